@@ -1,7 +1,11 @@
-use super::verify_input_file;
-use crate::CmdExecutor;
+use super::{verify_input_file, verify_output_path};
+use crate::{CmdExecutor, DataUri};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
+use std::fmt;
+use std::io::{BufReader, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecutor)]
@@ -13,34 +17,134 @@ pub enum Base64SubCommand {
   Decode(Base64DecodeOpts),
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum Base64Format {
+  Standard,
+  UrlSafe,
+}
+
 #[derive(Debug, Parser)]
 pub struct Base64EncodeOpts {
   #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
   pub input: String,
+
+  /// Alphabet: standard (`+/`) or urlsafe (`-_`)
+  #[arg(long, value_parser = parse_base64_format, default_value = "urlsafe")]
+  pub format: Base64Format,
+
+  /// Omit the trailing `=` padding
+  #[arg(long)]
+  pub no_padding: bool,
+
+  /// Break the output into lines of this width, 76 for MIME or 64 for PEM
+  #[arg(short, long)]
+  pub wrap: Option<usize>,
+
+  /// Emit a `data:<mime>;base64,...` URI, the MIME type is sniffed from the input
+  #[arg(long, conflicts_with_all = ["format", "no_padding", "wrap"])]
+  pub data_uri: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64DecodeOpts {
   #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
   pub input: String,
+
+  /// Only accept this alphabet: standard or urlsafe. Any variant is accepted when omitted
+  #[arg(long, value_parser = parse_base64_format)]
+  pub format: Option<Base64Format>,
+
+  /// Reject padded input, only with --format
+  #[arg(long, requires = "format")]
+  pub no_padding: bool,
+
+  /// Write the decoded bytes to this file instead of stdout. Data URIs are detected and decoded too
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
 }
 
 impl CmdExecutor for Base64EncodeOpts {
   async fn execute(self) -> anyhow::Result<()> {
     let mut reader = crate::get_reader(&self.input)?;
-    let ret = crate::process_encode(&mut reader)?;
-    print!("{}", ret);
+    let mut writer = crate::get_writer(None)?;
+    if self.data_uri {
+      let path = (self.input != "-").then_some(self.input.as_str());
+      let mime = crate::process_encode_data_uri(&mut reader, &mut writer, path)?;
+      eprintln!("MIME type: {}", mime);
+      return Ok(());
+    }
+    crate::process_encode(
+      &mut reader,
+      &mut writer,
+      self.format,
+      !self.no_padding,
+      self.wrap,
+    )?;
     Ok(())
   }
 }
 
 impl CmdExecutor for Base64DecodeOpts {
   async fn execute(self) -> anyhow::Result<()> {
-    let mut reader = crate::get_reader(&self.input)?;
-    let ret = crate::process_decode(&mut reader)?;
-    let ret = String::from_utf8(ret)?;
-    print!("{}", ret);
-    Ok(())
+    let mut reader = BufReader::new(crate::get_reader(&self.input)?);
+    let data_uri = crate::read_data_uri_header(&mut reader)?;
+    if let Some(uri) = &data_uri {
+      eprintln!("Data URI of type {}", uri.mime);
+    }
+    // decode to memory for the terminal so binary data can be previewed instead
+    if self.output.is_none() && std::io::stdout().is_terminal() {
+      let mut buf = Vec::new();
+      self.decode(&mut reader, &mut buf, data_uri.as_ref())?;
+      return crate::write_decoded(&buf, None);
+    }
+    let mut writer = crate::get_writer(self.output.as_deref())?;
+    self.decode(&mut reader, &mut writer, data_uri.as_ref())
+  }
+}
+
+impl Base64DecodeOpts {
+  fn decode(
+    &self,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    data_uri: Option<&DataUri>,
+  ) -> anyhow::Result<()> {
+    match (self.format, data_uri) {
+      (_, Some(uri)) if !uri.base64 => crate::process_decode_percent(reader, writer),
+      (Some(format), _) => crate::process_decode(reader, writer, format, !self.no_padding),
+      (None, _) => crate::process_decode_lenient(reader, writer),
+    }
+  }
+}
+
+fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
+  format.parse()
+}
+
+impl FromStr for Base64Format {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "standard" => Ok(Base64Format::Standard),
+      "urlsafe" => Ok(Base64Format::UrlSafe),
+      _ => Err(anyhow::anyhow!("Invalid format")),
+    }
+  }
+}
+
+impl From<Base64Format> for &'static str {
+  fn from(format: Base64Format) -> Self {
+    match format {
+      Base64Format::Standard => "standard",
+      Base64Format::UrlSafe => "urlsafe",
+    }
+  }
+}
+
+impl fmt::Display for Base64Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
   }
 }
 
@@ -0,0 +1,112 @@
+use super::{verify_input_file, verify_output_path};
+use crate::{CmdExecutor, get_reader, get_writer, process_codec_decode, process_codec_encode};
+use clap::Parser;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Codec {
+  Hex,
+  Hexdump,
+  Base32,
+  Crockford,
+  Base58,
+  Base58Check,
+  Ascii85,
+  Z85,
+  Base64,
+  Base64Url,
+}
+
+#[derive(Debug, Parser)]
+pub struct EncodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// hex, hexdump, base32, crockford, base58, base58check, ascii85, z85, base64 or base64url
+  #[arg(short, long, value_parser = parse_codec)]
+  pub codec: Codec,
+
+  /// Write the encoded text to this file instead of stdout
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct DecodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// hex, hexdump, base32, crockford, base58, base58check, ascii85, z85, base64 or base64url
+  #[arg(short, long, value_parser = parse_codec)]
+  pub codec: Codec,
+
+  /// Write the decoded bytes to this file instead of stdout
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+}
+
+impl CmdExecutor for EncodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let mut writer = get_writer(self.output.as_deref())?;
+    process_codec_encode(&mut reader, &mut writer, self.codec)
+  }
+}
+
+impl CmdExecutor for DecodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let mut buf = Vec::new();
+    process_codec_decode(&mut reader, &mut buf, self.codec)?;
+    crate::write_decoded(&buf, self.output.as_deref())
+  }
+}
+
+fn parse_codec(codec: &str) -> Result<Codec, anyhow::Error> {
+  codec.parse()
+}
+
+impl FromStr for Codec {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "hex" => Ok(Codec::Hex),
+      "hexdump" => Ok(Codec::Hexdump),
+      "base32" => Ok(Codec::Base32),
+      "crockford" => Ok(Codec::Crockford),
+      "base58" => Ok(Codec::Base58),
+      "base58check" => Ok(Codec::Base58Check),
+      "ascii85" => Ok(Codec::Ascii85),
+      "z85" => Ok(Codec::Z85),
+      "base64" => Ok(Codec::Base64),
+      "base64url" => Ok(Codec::Base64Url),
+      _ => Err(anyhow::anyhow!("Invalid codec")),
+    }
+  }
+}
+
+impl From<Codec> for &'static str {
+  fn from(codec: Codec) -> Self {
+    match codec {
+      Codec::Hex => "hex",
+      Codec::Hexdump => "hexdump",
+      Codec::Base32 => "base32",
+      Codec::Crockford => "crockford",
+      Codec::Base58 => "base58",
+      Codec::Base58Check => "base58check",
+      Codec::Ascii85 => "ascii85",
+      Codec::Z85 => "z85",
+      Codec::Base64 => "base64",
+      Codec::Base64Url => "base64url",
+    }
+  }
+}
+
+impl fmt::Display for Codec {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
@@ -1,7 +1,15 @@
-use super::verify_input_file;
-use crate::CmdExecutor;
+use super::{verify_input_file, verify_output_path};
+use crate::{
+  CmdExecutor, ColumnarOptions, CsvFmtOptions, DEFAULT_ROW_GROUP_SIZE, get_content, get_reader,
+  process_csv_fmt, process_csv_mask,
+};
+use anyhow::anyhow;
 use clap::Parser;
+use enum_dispatch::enum_dispatch;
 use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy)]
@@ -9,12 +17,54 @@ pub enum OutputFormat {
   Json,
   Yaml,
   Toml,
+  Parquet,
+  Arrow,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsvCompression {
+  None,
+  Snappy,
+  Gzip,
+  Zstd,
+  Lz4,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LineEnding {
+  Lf,
+  Crlf,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsvQuoteStyle {
+  Necessary,
+  Always,
+  NonNumeric,
+}
+
+#[derive(Debug, Clone)]
+pub enum MaskStrategy {
+  Hash,
+  Redact,
+  Fake,
+  Truncate(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskRule {
+  pub column: String,
+  pub strategy: MaskStrategy,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-  #[arg(short, long, value_parser = verify_input_file)]
-  pub input: String,
+  #[command(subcommand)]
+  pub cmd: Option<CsvSubCommand>,
+
+  #[arg(short, long, value_parser = verify_input_file, required = true)]
+  pub input: Option<String>,
 
   #[arg(short, long)]
   pub output: Option<String>,
@@ -22,21 +72,141 @@ pub struct CsvOpts {
   #[arg(long, value_parser = parse_format, default_value = "json")]
   pub format: OutputFormat,
 
-  #[arg(short, long, default_value_t = ',')]
-  pub delimiter: char,
+  /// Field delimiter, a single ASCII character
+  #[arg(short, long, value_parser = parse_delimiter, default_value = ",")]
+  pub delimiter: u8,
 
-  #[arg(long, default_value_t = true)]
+  /// Whether the first row holds the column names
+  #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
   pub header: bool,
+
+  /// Rows per Parquet row group
+  #[arg(long, default_value_t = DEFAULT_ROW_GROUP_SIZE)]
+  pub row_group_size: usize,
+
+  /// Compression for Parquet (none, snappy, gzip, zstd, lz4) or Arrow (none, zstd, lz4) output
+  #[arg(long, value_parser = parse_compression, default_value = "zstd")]
+  pub compression: CsvCompression,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum CsvSubCommand {
+  #[command(about = "Mask sensitive columns before sharing a CSV")]
+  Mask(CsvMaskOpts),
+
+  #[command(about = "Re-emit a CSV with a consistent dialect")]
+  Fmt(CsvFmtOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMaskOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+
+  /// Field delimiter, a single ASCII character
+  #[arg(short, long, value_parser = parse_delimiter, default_value = ",")]
+  pub delimiter: u8,
+
+  /// Column rule as <column>=<hash|redact|fake|truncate:N>, may be repeated
+  #[arg(short, long = "rule", value_parser = parse_mask_rule, required = true)]
+  pub rules: Vec<MaskRule>,
+
+  /// BLAKE3 key file used by the hash strategy, e.g. one from `rcli text generate`
+  #[arg(short, long, value_parser = verify_input_file)]
+  pub key: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFmtOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+
+  /// Delimiter of the input file, a single ASCII character
+  #[arg(short, long, value_parser = parse_delimiter, default_value = ",")]
+  pub delimiter: u8,
+
+  /// Delimiter of the output, defaults to the input delimiter
+  #[arg(long, value_parser = parse_delimiter)]
+  pub out_delimiter: Option<u8>,
+
+  /// Record terminator, also applied to line breaks inside quoted fields
+  #[arg(long, value_parser = parse_line_ending, default_value = "lf")]
+  pub line_ending: LineEnding,
+
+  #[arg(long, value_parser = parse_quote_style, default_value = "necessary")]
+  pub quote: CsvQuoteStyle,
+
+  /// Strip trailing whitespace from every field
+  #[arg(long)]
+  pub trim: bool,
+}
+
+impl CmdExecutor for CsvFmtOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let mut writer: Box<dyn Write> = match self.output {
+      Some(path) => Box::new(File::create(path)?),
+      None => Box::new(std::io::stdout()),
+    };
+    let options = CsvFmtOptions {
+      delimiter: self.delimiter,
+      out_delimiter: self.out_delimiter.unwrap_or(self.delimiter),
+      line_ending: self.line_ending,
+      quote: self.quote,
+      trim: self.trim,
+    };
+    process_csv_fmt(&mut reader, &mut writer, options)
+  }
+}
+
+impl CmdExecutor for CsvMaskOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let key = self.key.as_deref().map(get_content).transpose()?;
+    let mut writer: Box<dyn Write> = match self.output {
+      Some(path) => Box::new(File::create(path)?),
+      None => Box::new(std::io::stdout()),
+    };
+    process_csv_mask(
+      &mut reader,
+      &mut writer,
+      &self.rules,
+      key.as_deref(),
+      self.delimiter,
+    )
+  }
 }
 
 impl CmdExecutor for CsvOpts {
   async fn execute(self) -> anyhow::Result<()> {
+    if let Some(cmd) = self.cmd {
+      return cmd.execute().await;
+    }
+    let input = self.input.ok_or_else(|| anyhow!("Missing input file"))?;
     let output = if let Some(output) = self.output {
       output
     } else {
       format!("output.{}", self.format)
     };
-    crate::process_csv(&self.input, output, self.format)
+    let options = ColumnarOptions {
+      row_group_size: self.row_group_size,
+      compression: self.compression,
+    };
+    crate::process_csv_with_options(
+      &input,
+      output,
+      self.format,
+      self.delimiter,
+      self.header,
+      options,
+    )
   }
 }
 
@@ -44,12 +214,38 @@ fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
   format.parse::<OutputFormat>()
 }
 
+fn parse_compression(compression: &str) -> Result<CsvCompression, anyhow::Error> {
+  compression.parse::<CsvCompression>()
+}
+
+fn parse_line_ending(line_ending: &str) -> Result<LineEnding, anyhow::Error> {
+  line_ending.parse::<LineEnding>()
+}
+
+fn parse_quote_style(quote: &str) -> Result<CsvQuoteStyle, anyhow::Error> {
+  quote.parse::<CsvQuoteStyle>()
+}
+
+// csv works on bytes, a multi-byte char would be silently truncated
+fn parse_delimiter(delimiter: &str) -> Result<u8, anyhow::Error> {
+  match delimiter.chars().collect::<Vec<_>>()[..] {
+    [c] if c.is_ascii() => Ok(c as u8),
+    _ => Err(anyhow!("Delimiter must be a single ASCII character")),
+  }
+}
+
+fn parse_mask_rule(rule: &str) -> Result<MaskRule, anyhow::Error> {
+  rule.parse::<MaskRule>()
+}
+
 impl From<OutputFormat> for &'static str {
   fn from(format: OutputFormat) -> Self {
     match format {
       OutputFormat::Json => "json",
       OutputFormat::Yaml => "yaml",
       OutputFormat::Toml => "toml",
+      OutputFormat::Parquet => "parquet",
+      OutputFormat::Arrow => "arrow",
     }
   }
 }
@@ -62,6 +258,8 @@ impl FromStr for OutputFormat {
       "json" => Ok(OutputFormat::Json),
       "yaml" => Ok(OutputFormat::Yaml),
       "toml" => Ok(OutputFormat::Toml),
+      "parquet" => Ok(OutputFormat::Parquet),
+      "arrow" => Ok(OutputFormat::Arrow),
       _ => Err(anyhow::anyhow!("Invalid format")),
     }
   }
@@ -72,3 +270,114 @@ impl fmt::Display for OutputFormat {
     write!(f, "{}", Into::<&str>::into(*self))
   }
 }
+
+impl From<CsvCompression> for &'static str {
+  fn from(compression: CsvCompression) -> Self {
+    match compression {
+      CsvCompression::None => "none",
+      CsvCompression::Snappy => "snappy",
+      CsvCompression::Gzip => "gzip",
+      CsvCompression::Zstd => "zstd",
+      CsvCompression::Lz4 => "lz4",
+    }
+  }
+}
+
+impl FromStr for CsvCompression {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(CsvCompression::None),
+      "snappy" => Ok(CsvCompression::Snappy),
+      "gzip" => Ok(CsvCompression::Gzip),
+      "zstd" => Ok(CsvCompression::Zstd),
+      "lz4" => Ok(CsvCompression::Lz4),
+      _ => Err(anyhow::anyhow!("Invalid compression")),
+    }
+  }
+}
+
+impl fmt::Display for CsvCompression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&str>::into(*self))
+  }
+}
+
+impl FromStr for LineEnding {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "lf" => Ok(LineEnding::Lf),
+      "crlf" => Ok(LineEnding::Crlf),
+      _ => Err(anyhow!("Invalid line ending")),
+    }
+  }
+}
+
+impl FromStr for CsvQuoteStyle {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "necessary" => Ok(CsvQuoteStyle::Necessary),
+      "always" => Ok(CsvQuoteStyle::Always),
+      "non-numeric" => Ok(CsvQuoteStyle::NonNumeric),
+      _ => Err(anyhow!("Invalid quote style")),
+    }
+  }
+}
+
+impl FromStr for MaskStrategy {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "hash" => Ok(MaskStrategy::Hash),
+      "redact" => Ok(MaskStrategy::Redact),
+      "fake" => Ok(MaskStrategy::Fake),
+      _ => match s.strip_prefix("truncate:") {
+        Some(n) => Ok(MaskStrategy::Truncate(n.parse()?)),
+        None => Err(anyhow!("Invalid mask strategy")),
+      },
+    }
+  }
+}
+
+impl FromStr for MaskRule {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (column, strategy) = s
+      .rsplit_once('=')
+      .ok_or_else(|| anyhow!("Mask rule must look like <column>=<strategy>"))?;
+    Ok(MaskRule {
+      column: column.to_string(),
+      strategy: strategy.parse()?,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_mask_rule() {
+    let rule: MaskRule = "email=truncate:3".parse().unwrap();
+    assert_eq!(rule.column, "email");
+    assert!(matches!(rule.strategy, MaskStrategy::Truncate(3)));
+    assert!("email".parse::<MaskRule>().is_err());
+    assert!("email=scramble".parse::<MaskRule>().is_err());
+  }
+
+  #[test]
+  fn test_parse_delimiter() {
+    assert_eq!(parse_delimiter(";").unwrap(), b';');
+    assert_eq!(parse_delimiter("\t").unwrap(), b'\t');
+    assert!(parse_delimiter("§").is_err());
+    assert!(parse_delimiter(";;").is_err());
+    assert!(parse_delimiter("").is_err());
+  }
+}
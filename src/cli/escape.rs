@@ -0,0 +1,143 @@
+use super::{verify_input_file, verify_output_path};
+use crate::{
+  CmdExecutor, get_reader, get_writer, process_html_escape, process_html_unescape,
+  process_qp_decode, process_qp_encode, process_url_decode, process_url_encode, write_decoded,
+};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum UrlSubCommand {
+  #[command(
+    name = "encode",
+    about = "Percent-encode a URL component or a full URL"
+  )]
+  Encode(UrlEncodeOpts),
+
+  #[command(name = "decode", about = "Decode a percent-encoded string")]
+  Decode(UrlDecodeOpts),
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum HtmlSubCommand {
+  #[command(name = "escape", about = "Escape HTML special characters")]
+  Escape(HtmlEscapeOpts),
+
+  #[command(name = "unescape", about = "Unescape named and numeric HTML entities")]
+  Unescape(HtmlUnescapeOpts),
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum QpSubCommand {
+  #[command(name = "encode", about = "Quoted-printable encode")]
+  Encode(QpEncodeOpts),
+
+  #[command(name = "decode", about = "Quoted-printable decode")]
+  Decode(QpDecodeOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlEncodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// Encode a full URL, keeping delimiters such as `:/?#&=` instead of escaping them
+  #[arg(long)]
+  pub full: bool,
+
+  /// Encode spaces as `+`, as in HTML form data
+  #[arg(long)]
+  pub form: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlDecodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// Decode `+` as a space, as in HTML form data
+  #[arg(long)]
+  pub form: bool,
+
+  /// Write the decoded bytes to this file instead of stdout
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct HtmlEscapeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct HtmlUnescapeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct QpEncodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct QpDecodeOpts {
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// Write the decoded bytes to this file instead of stdout
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+}
+
+impl CmdExecutor for UrlEncodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    process_url_encode(&mut reader, &mut get_writer(None)?, self.full, self.form)
+  }
+}
+
+impl CmdExecutor for UrlDecodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let mut buf = Vec::new();
+    process_url_decode(&mut reader, &mut buf, self.form)?;
+    write_decoded(&buf, self.output.as_deref())
+  }
+}
+
+impl CmdExecutor for HtmlEscapeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    process_html_escape(&mut reader, &mut get_writer(None)?)
+  }
+}
+
+impl CmdExecutor for HtmlUnescapeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    process_html_unescape(&mut reader, &mut get_writer(None)?)
+  }
+}
+
+impl CmdExecutor for QpEncodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    process_qp_encode(&mut reader, &mut get_writer(None)?)
+  }
+}
+
+impl CmdExecutor for QpDecodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let mut buf = Vec::new();
+    process_qp_decode(&mut reader, &mut buf)?;
+    write_decoded(&buf, self.output.as_deref())
+  }
+}
@@ -1,39 +1,646 @@
-use clap::Parser;
-use zxcvbn::zxcvbn;
+use clap::{Args, Parser};
+use enum_dispatch::enum_dispatch;
+use rand::RngCore;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 
-use crate::{CmdExecutor, process_genpass};
+use super::{verify_input_file, verify_output_path};
+use crate::{
+  BreachList, CmdExecutor, GenPassOptions, PassphraseOptions, PasswordPolicy, PasswordReport,
+  genpass_entropy, genpass_rng, get_content, get_reader, password_report, process_check_passwords,
+  process_genpass_with_policy, process_genpass_with_rng, process_passphrase_with_rng,
+  process_pattern, process_pin, process_pronounceable, process_token, write_secret_file,
+};
+
+const MAX_BREACH_ATTEMPTS: usize = 100;
+// --pronounceable and --pattern pick their own characters, the class and
+// alphabet options would be silently ignored
+const TEMPLATE_CONFLICTS: [&str; 10] = [
+  "passphrase",
+  "policy",
+  "alphabet",
+  "no_uppercase",
+  "no_lowercase",
+  "no_number",
+  "no_symbol",
+  "symbols",
+  "exclude",
+  "allow_ambiguous",
+];
+// a passphrase is made of whole words, none of the character options apply
+const PASSPHRASE_CONFLICTS: [&str; 10] = [
+  "length",
+  "policy",
+  "alphabet",
+  "no_uppercase",
+  "no_lowercase",
+  "no_number",
+  "no_symbol",
+  "symbols",
+  "exclude",
+  "allow_ambiguous",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum GenPassFormat {
+  Plain,
+  Json,
+  Csv,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TokenKind {
+  Hex,
+  Base64,
+  ApiKey,
+  Uuid,
+  Uuid7,
+  Ulid,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BreachHashFormat {
+  Sha1,
+  Ntlm,
+}
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
+  #[command(subcommand)]
+  pub cmd: Option<GenPassSubCommand>,
+
   #[arg(short, long, default_value_t = 16)]
   pub length: u8,
 
-  #[arg(long, default_value_t = true)]
+  /// Number of passwords to generate
+  #[arg(short, long, default_value_t = 1)]
+  pub count: usize,
+
+  /// Output format: plain, json or csv
+  #[arg(long, value_parser = parse_format, default_value = "plain")]
+  pub format: GenPassFormat,
+
+  /// Exclude uppercase letters
+  #[arg(long)]
   pub no_uppercase: bool,
 
-  #[arg(long, default_value_t = true)]
+  /// Exclude lowercase letters
+  #[arg(long)]
   pub no_lowercase: bool,
 
-  #[arg(long, default_value_t = true)]
+  /// Exclude numbers
+  #[arg(long)]
   pub no_number: bool,
 
-  #[arg(long, default_value_t = true)]
+  /// Exclude symbols
+  #[arg(long)]
   pub no_symbol: bool,
+
+  /// Custom symbol set, replaces the default `!@#$%&^*_`
+  #[arg(long, conflicts_with = "no_symbol", allow_hyphen_values = true)]
+  pub symbols: Option<String>,
+
+  /// Draw every character from this alphabet instead of the character classes
+  #[arg(
+    long,
+    allow_hyphen_values = true,
+    conflicts_with_all = ["no_uppercase", "no_lowercase", "no_number", "no_symbol", "symbols"]
+  )]
+  pub alphabet: Option<String>,
+
+  /// Characters that must never appear in the password
+  #[arg(long, default_value = "", allow_hyphen_values = true)]
+  pub exclude: String,
+
+  /// Allow look-alike characters such as 0/O and 1/l in the built-in classes
+  #[arg(long)]
+  pub allow_ambiguous: bool,
+
+  /// YAML or TOML policy file the generated password must satisfy
+  #[arg(long, value_parser = verify_input_file, conflicts_with = "passphrase")]
+  pub policy: Option<String>,
+
+  /// Local HIBP style hash list (sorted `HASH:COUNT` lines) of breached passwords to avoid
+  #[arg(long, value_parser = verify_input_file)]
+  pub breach_list: Option<String>,
+
+  /// Hash format of the breach list: sha1 or ntlm
+  #[arg(long, value_parser = parse_breach_format, default_value = "sha1")]
+  pub breach_format: BreachHashFormat,
+
+  #[command(flatten)]
+  pub seed: SeedOpts,
+
+  /// Generate a diceware passphrase instead of a password
+  #[arg(long, conflicts_with_all = PASSPHRASE_CONFLICTS)]
+  pub passphrase: bool,
+
+  /// Generate a pronounceable password of alternating consonants and vowels
+  #[arg(long, conflicts_with_all = TEMPLATE_CONFLICTS)]
+  pub pronounceable: bool,
+
+  /// Generate from a template: c/C consonant, v/V vowel, a/A letter, 9 digit, ! symbol, \x literal
+  #[arg(
+    long,
+    allow_hyphen_values = true,
+    conflicts_with_all = TEMPLATE_CONFLICTS,
+    conflicts_with = "pronounceable"
+  )]
+  pub pattern: Option<String>,
+
+  /// Number of words in the passphrase
+  #[arg(long, default_value_t = 6, requires = "passphrase")]
+  pub words: usize,
+
+  /// Wordlist file, one word per line, defaults to the EFF large wordlist
+  #[arg(long, value_parser = verify_input_file, requires = "passphrase")]
+  pub wordlist: Option<String>,
+
+  #[arg(long, default_value = "-", requires = "passphrase")]
+  pub separator: String,
+
+  /// Capitalize the first letter of every word
+  #[arg(long, requires = "passphrase")]
+  pub capitalize: bool,
+
+  /// Append a random digit to one of the words
+  #[arg(long, requires = "passphrase")]
+  pub digit: bool,
+
+  /// Write the passwords to this file, created with 0600 permissions, instead
+  /// of stdout, only the score summary goes to stderr
+  #[arg(short, long, value_parser = verify_output_path)]
+  pub output: Option<PathBuf>,
+
+  /// Also print the passwords to stdout when writing them to `--output`
+  #[arg(long, requires = "output")]
+  pub echo: bool,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum GenPassSubCommand {
+  #[command(about = "Check the strength of existing passwords")]
+  Check(GenPassCheckOpts),
+
+  #[command(about = "Generate numeric PINs, rejecting easily guessed ones")]
+  Pin(GenPassPinOpts),
+
+  #[command(about = "Generate random tokens, API keys, UUIDs and ULIDs")]
+  Token(GenPassTokenOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassTokenOpts {
+  /// Token kind: hex, base64 (url safe), api-key, uuid (v4), uuid7 or ulid
+  #[arg(short, long, value_parser = parse_token_kind, default_value = "hex")]
+  pub kind: TokenKind,
+
+  /// Random bytes in hex and base64 tokens
+  #[arg(short, long, default_value_t = 32)]
+  pub bytes: usize,
+
+  /// Prefix of API keys, e.g. `sk` gives `sk_...`
+  #[arg(short, long, default_value = "rcli")]
+  pub prefix: String,
+
+  /// Number of tokens to generate
+  #[arg(short, long, default_value_t = 1)]
+  pub count: usize,
+
+  #[command(flatten)]
+  pub seed: SeedOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassPinOpts {
+  #[arg(short, long, default_value_t = 6)]
+  pub length: u8,
+
+  /// Number of PINs to generate
+  #[arg(short, long, default_value_t = 1)]
+  pub count: usize,
+
+  #[command(flatten)]
+  pub seed: SeedOpts,
+}
+
+#[derive(Debug, Args)]
+pub struct SeedOpts {
+  /// INSECURE: seed the generator for reproducible output, only for test fixtures
+  #[arg(long)]
+  pub seed: Option<u64>,
+}
+
+impl SeedOpts {
+  /// The generator to draw from, warning on stderr when it is seeded.
+  pub fn rng(&self) -> Box<dyn RngCore> {
+    if self.seed.is_some() {
+      eprintln!("WARNING: --seed makes the output predictable, never use it for real secrets");
+    }
+    genpass_rng(self.seed)
+  }
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassCheckOpts {
+  /// File with one candidate password per line
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// Context word such as a user or company name, may be repeated
+  #[arg(short, long = "user-input")]
+  pub user_inputs: Vec<String>,
+
+  /// Fail when any password scores below this (0-4)
+  #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+  pub min_score: Option<u8>,
+
+  /// Local HIBP style hash list (sorted `HASH:COUNT` lines) of breached passwords, any hit fails the check
+  #[arg(long, value_parser = verify_input_file)]
+  pub breach_list: Option<String>,
+
+  /// Hash format of the breach list: sha1 or ntlm
+  #[arg(long, value_parser = parse_breach_format, default_value = "sha1")]
+  pub breach_format: BreachHashFormat,
+
+  /// Output format: plain, json or csv
+  #[arg(long, value_parser = parse_format, default_value = "plain")]
+  pub format: GenPassFormat,
+}
+
+impl CmdExecutor for GenPassPinOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut rng = self.seed.rng();
+    for _ in 0..self.count {
+      println!("{}", process_pin(self.length, &mut rng)?);
+    }
+    Ok(())
+  }
+}
+
+impl CmdExecutor for GenPassTokenOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut rng = self.seed.rng();
+    for _ in 0..self.count {
+      println!(
+        "{}",
+        process_token(self.kind, self.bytes, &self.prefix, &mut rng)?
+      );
+    }
+    Ok(())
+  }
+}
+
+impl CmdExecutor for GenPassCheckOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let mut reader = get_reader(&self.input)?;
+    let user_inputs: Vec<&str> = self.user_inputs.iter().map(String::as_str).collect();
+    let mut breach_list = self
+      .breach_list
+      .map(|path| BreachList::open(path, self.breach_format))
+      .transpose()?;
+    let reports = process_check_passwords(&mut reader, &user_inputs, breach_list.as_mut())?;
+    match self.format {
+      GenPassFormat::Plain => {
+        for report in &reports {
+          println!(
+            "line {}: score {}, crack time {} (offline, slow hash)",
+            report.line, report.score, report.crack_times.offline_slow_hash
+          );
+          if let Some(warning) = &report.warning {
+            println!("  warning: {}", warning);
+          }
+          for suggestion in &report.suggestions {
+            println!("  suggestion: {}", suggestion);
+          }
+          if let Some(count @ 1..) = report.breached {
+            println!("  breached: seen {} times", count);
+          }
+        }
+      }
+      GenPassFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+      GenPassFormat::Csv => {
+        let mut writer = csv::Writer::from_writer(std::io::stdout());
+        writer.write_record([
+          "line",
+          "score",
+          "warning",
+          "suggestions",
+          "online_throttled",
+          "online_unthrottled",
+          "offline_slow_hash",
+          "offline_fast_hash",
+          "breached",
+        ])?;
+        for report in &reports {
+          writer.write_record([
+            report.line.to_string(),
+            report.score.to_string(),
+            report.warning.clone().unwrap_or_default(),
+            report.suggestions.join(" "),
+            report.crack_times.online_throttled.clone(),
+            report.crack_times.online_unthrottled.clone(),
+            report.crack_times.offline_slow_hash.clone(),
+            report.crack_times.offline_fast_hash.clone(),
+            report.breached.map(|c| c.to_string()).unwrap_or_default(),
+          ])?;
+        }
+        writer.flush()?;
+      }
+    }
+
+    if let Some(min_score) = self.min_score {
+      let weak = reports.iter().filter(|r| r.score < min_score).count();
+      if weak > 0 {
+        anyhow::bail!("{} password(s) scored below {}", weak, min_score);
+      }
+    }
+    let breached = reports.iter().filter(|r| r.breached > Some(0)).count();
+    if breached > 0 {
+      anyhow::bail!("{} password(s) found in the breach list", breached);
+    }
+    Ok(())
+  }
 }
 
 impl CmdExecutor for GenPassOpts {
   async fn execute(self) -> anyhow::Result<()> {
-    let ret = process_genpass(
-      self.length,
-      self.no_uppercase,
-      self.no_lowercase,
-      self.no_number,
-      self.no_symbol,
-    )?;
-    print!("{}", ret);
-
-    let estimate = zxcvbn(&ret, &[])?;
-    eprintln!("\nPassword score: {}", estimate.score());
+    if let Some(cmd) = self.cmd {
+      return cmd.execute().await;
+    }
+    if self.count == 0 {
+      anyhow::bail!("Count must be at least 1");
+    }
+    let format = self.format;
+    let output = self.output.clone();
+    let echo = output.is_none() || self.echo;
+    let reports = self.generate()?;
+    let content = match format {
+      GenPassFormat::Plain => reports
+        .iter()
+        .map(|r| format!("{}\n", r.password))
+        .collect(),
+      GenPassFormat::Json => format!("{}\n", serde_json::to_string_pretty(&reports)?),
+      GenPassFormat::Csv => {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for report in &reports {
+          writer.serialize(report)?;
+        }
+        String::from_utf8(writer.into_inner()?)?
+      }
+    };
+    if let Some(path) = &output {
+      write_secret_file(path, content.as_bytes())?;
+    }
+    if echo {
+      print!("{}", content);
+    }
+    // json and csv carry the scores themselves, unless they never reach the terminal
+    if matches!(format, GenPassFormat::Plain) || !echo {
+      for report in &reports {
+        eprintln!(
+          "Password score: {}, entropy: {:.1} bits, crack time: {}",
+          report.score, report.entropy, report.crack_time
+        );
+      }
+    }
+    if let Some(path) = output {
+      eprintln!("Wrote {} password(s) to {}", reports.len(), path.display());
+    }
     Ok(())
   }
 }
+
+impl GenPassOpts {
+  fn generate(self) -> anyhow::Result<Vec<PasswordReport>> {
+    let mut rng = self.seed.rng();
+    let mut breach_list = self
+      .breach_list
+      .as_deref()
+      .map(|path| BreachList::open(path, self.breach_format))
+      .transpose()?;
+    // regenerate anything that shows up in the breach list
+    let mut unbreached = |next: &mut dyn FnMut() -> anyhow::Result<(String, f64)>| {
+      for _ in 0..MAX_BREACH_ATTEMPTS {
+        let (password, entropy) = next()?;
+        if let Some(list) = breach_list.as_mut()
+          && list.lookup(&password)?.is_some()
+        {
+          continue;
+        }
+        return password_report(password, entropy);
+      }
+      anyhow::bail!(
+        "Every generated password was in the breach list after {} attempts",
+        MAX_BREACH_ATTEMPTS
+      )
+    };
+
+    if self.passphrase {
+      let wordlist = self.wordlist.as_deref().map(get_content).transpose()?;
+      let wordlist = wordlist.map(String::from_utf8).transpose()?;
+      let options = PassphraseOptions {
+        words: self.words,
+        separator: &self.separator,
+        capitalize: self.capitalize,
+        digit: self.digit,
+      };
+      return (0..self.count)
+        .map(|_| {
+          unbreached(&mut || {
+            let ret = process_passphrase_with_rng(wordlist.as_deref(), &options, &mut rng)?;
+            Ok((ret.passphrase, ret.entropy))
+          })
+        })
+        .collect();
+    }
+
+    if self.pronounceable {
+      return (0..self.count)
+        .map(|_| unbreached(&mut || process_pronounceable(self.length, &mut rng)))
+        .collect();
+    }
+    if let Some(pattern) = &self.pattern {
+      return (0..self.count)
+        .map(|_| unbreached(&mut || process_pattern(pattern, &mut rng)))
+        .collect();
+    }
+
+    let options = GenPassOptions {
+      length: self.length,
+      upper: !self.no_uppercase,
+      lower: !self.no_lowercase,
+      number: !self.no_number,
+      symbol: !self.no_symbol,
+      symbols: self.symbols,
+      alphabet: self.alphabet,
+      exclude: self.exclude,
+      allow_ambiguous: self.allow_ambiguous,
+    };
+    let policy = self.policy.map(PasswordPolicy::load).transpose()?;
+    let entropy = match &policy {
+      Some(policy) => policy.entropy(&options)?,
+      None => genpass_entropy(&options)?,
+    };
+    (0..self.count)
+      .map(|_| {
+        unbreached(&mut || {
+          let password = match &policy {
+            Some(policy) => process_genpass_with_policy(&options, policy, &mut rng)?,
+            None => process_genpass_with_rng(&options, &mut rng)?,
+          };
+          Ok((password, entropy))
+        })
+      })
+      .collect()
+  }
+}
+
+fn parse_format(format: &str) -> Result<GenPassFormat, anyhow::Error> {
+  format.parse()
+}
+
+impl FromStr for GenPassFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "plain" => Ok(GenPassFormat::Plain),
+      "json" => Ok(GenPassFormat::Json),
+      "csv" => Ok(GenPassFormat::Csv),
+      _ => Err(anyhow::anyhow!("Invalid format")),
+    }
+  }
+}
+
+impl From<GenPassFormat> for &'static str {
+  fn from(format: GenPassFormat) -> Self {
+    match format {
+      GenPassFormat::Plain => "plain",
+      GenPassFormat::Json => "json",
+      GenPassFormat::Csv => "csv",
+    }
+  }
+}
+
+impl fmt::Display for GenPassFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
+
+fn parse_breach_format(format: &str) -> Result<BreachHashFormat, anyhow::Error> {
+  format.parse()
+}
+
+impl FromStr for BreachHashFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "sha1" => Ok(BreachHashFormat::Sha1),
+      "ntlm" => Ok(BreachHashFormat::Ntlm),
+      _ => Err(anyhow::anyhow!("Invalid breach list format")),
+    }
+  }
+}
+
+impl From<BreachHashFormat> for &'static str {
+  fn from(format: BreachHashFormat) -> Self {
+    match format {
+      BreachHashFormat::Sha1 => "sha1",
+      BreachHashFormat::Ntlm => "ntlm",
+    }
+  }
+}
+
+impl fmt::Display for BreachHashFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
+
+fn parse_token_kind(kind: &str) -> Result<TokenKind, anyhow::Error> {
+  kind.parse()
+}
+
+impl FromStr for TokenKind {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "hex" => Ok(TokenKind::Hex),
+      "base64" => Ok(TokenKind::Base64),
+      "api-key" => Ok(TokenKind::ApiKey),
+      "uuid" => Ok(TokenKind::Uuid),
+      "uuid7" => Ok(TokenKind::Uuid7),
+      "ulid" => Ok(TokenKind::Ulid),
+      _ => Err(anyhow::anyhow!("Invalid token kind")),
+    }
+  }
+}
+
+impl From<TokenKind> for &'static str {
+  fn from(kind: TokenKind) -> Self {
+    match kind {
+      TokenKind::Hex => "hex",
+      TokenKind::Base64 => "base64",
+      TokenKind::ApiKey => "api-key",
+      TokenKind::Uuid => "uuid",
+      TokenKind::Uuid7 => "uuid7",
+      TokenKind::Ulid => "ulid",
+    }
+  }
+}
+
+impl fmt::Display for TokenKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Opts;
+  use clap::Parser;
+
+  fn parse(args: &[&str]) -> Result<Opts, clap::Error> {
+    Opts::try_parse_from(["rcli", "genpass"].iter().chain(args))
+  }
+
+  #[test]
+  fn test_passphrase_conflicts() {
+    assert!(parse(&["--passphrase", "--words", "5", "--digit"]).is_ok());
+    for args in [
+      &["--passphrase", "--alphabet", "abc"][..],
+      &["--passphrase", "-l", "5"],
+      &["--passphrase", "--no-symbol"],
+      &["--passphrase", "--symbols", "+-"],
+      &["--passphrase", "--exclude", "a"],
+      &["--passphrase", "--allow-ambiguous"],
+    ] {
+      assert!(parse(args).is_err(), "{:?} should be rejected", args);
+    }
+  }
+
+  #[test]
+  fn test_seed_is_shared() {
+    for args in [
+      &["--seed", "1"][..],
+      &["pin", "--seed", "1"],
+      &["token", "--seed", "1"],
+    ] {
+      assert!(parse(args).is_ok(), "{:?} should parse", args);
+    }
+    assert!(parse(&["pin", "--seed", "x"]).is_err());
+  }
+
+  #[test]
+  fn test_echo_requires_output() {
+    assert!(parse(&["--output", "secrets.txt", "--echo"]).is_ok());
+    assert!(parse(&["--echo"]).is_err());
+  }
+}
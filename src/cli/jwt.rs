@@ -0,0 +1,195 @@
+use super::verify_input_file;
+use crate::{
+  CmdExecutor, JwtValidation, get_content, jwt_claims, jwt_time_claims, process_jwt_decode,
+  process_jwt_sign, process_jwt_verify, unix_time,
+};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum JwtSubCommand {
+  #[command(
+    about = "Show the header and claims of a JWT and check exp/nbf/iat, without verifying it"
+  )]
+  Decode(JwtDecodeOpts),
+
+  #[command(about = "Sign claims into a JWT")]
+  Sign(JwtSignOpts),
+
+  #[command(about = "Verify the signature and claims of a JWT")]
+  Verify(JwtVerifyOpts),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum JwtAlgorithm {
+  Hs256,
+  EdDsa,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtDecodeOpts {
+  /// File holding the token
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtSignOpts {
+  /// HS256 secret (e.g. blake3.txt) or Ed25519 signing key (ed25519.sk) from `text generate`
+  #[arg(short, long, value_parser = verify_input_file)]
+  pub key: String,
+
+  /// hs256 or eddsa
+  #[arg(long, value_parser = parse_algorithm, default_value = "hs256")]
+  pub alg: JwtAlgorithm,
+
+  /// Claims as a JSON object
+  #[arg(short, long)]
+  pub claims: Option<String>,
+
+  #[arg(long)]
+  pub sub: Option<String>,
+
+  #[arg(long)]
+  pub iss: Option<String>,
+
+  #[arg(long)]
+  pub aud: Option<String>,
+
+  /// Lifetime of the token, e.g. 3600, 30m, 12h or 7d
+  #[arg(long, value_parser = parse_duration)]
+  pub exp: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtVerifyOpts {
+  /// File holding the token
+  #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+  pub input: String,
+
+  /// HS256 secret or Ed25519 verifying key (ed25519.pk)
+  #[arg(short, long, value_parser = verify_input_file)]
+  pub key: String,
+
+  /// Expected algorithm, tokens signed with any other are rejected
+  #[arg(long, value_parser = parse_algorithm, default_value = "hs256")]
+  pub alg: JwtAlgorithm,
+
+  /// Require this audience in the aud claim
+  #[arg(long)]
+  pub aud: Option<String>,
+
+  /// Require this issuer
+  #[arg(long)]
+  pub iss: Option<String>,
+
+  /// Allowed clock skew in seconds for exp/nbf/iat
+  #[arg(long, default_value_t = 60)]
+  pub leeway: u64,
+}
+
+impl CmdExecutor for JwtDecodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let token = String::from_utf8(get_content(&self.input)?)?;
+    let jwt = process_jwt_decode(&token)?;
+    println!("{}", serde_json::to_string_pretty(&jwt)?);
+    for claim in jwt_time_claims(&jwt.claims, unix_time()?, 0) {
+      eprintln!(
+        "{}{}: {} ({})",
+        if claim.valid { "" } else { "WARNING: " },
+        claim.name,
+        claim.value,
+        claim.note
+      );
+    }
+    Ok(())
+  }
+}
+
+impl CmdExecutor for JwtSignOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let key = get_content(&self.key)?;
+    let claims = jwt_claims(
+      self.claims.as_deref(),
+      unix_time()?,
+      self.exp,
+      &[
+        ("sub", self.sub.as_deref()),
+        ("iss", self.iss.as_deref()),
+        ("aud", self.aud.as_deref()),
+      ],
+    )?;
+    println!("{}", process_jwt_sign(&claims, &key, self.alg)?);
+    Ok(())
+  }
+}
+
+impl CmdExecutor for JwtVerifyOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let token = String::from_utf8(get_content(&self.input)?)?;
+    let key = get_content(&self.key)?;
+    let validation = JwtValidation {
+      now: unix_time()?,
+      leeway: self.leeway,
+      audience: self.aud,
+      issuer: self.iss,
+    };
+    let claims = process_jwt_verify(&token, &key, self.alg, &validation)?;
+    eprintln!("Token verified");
+    println!("{}", serde_json::to_string_pretty(&claims)?);
+    Ok(())
+  }
+}
+
+fn parse_duration(duration: &str) -> Result<u64, anyhow::Error> {
+  let (num, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+    Some(i) => duration.split_at(i),
+    None => (duration, "s"),
+  };
+  let unit = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 3600,
+    "d" => 86400,
+    _ => anyhow::bail!("Invalid duration unit, use s, m, h or d"),
+  };
+  num
+    .parse::<u64>()?
+    .checked_mul(unit)
+    .ok_or_else(|| anyhow::anyhow!("Duration is too long"))
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<JwtAlgorithm, anyhow::Error> {
+  algorithm.parse()
+}
+
+impl FromStr for JwtAlgorithm {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "hs256" => Ok(JwtAlgorithm::Hs256),
+      "eddsa" => Ok(JwtAlgorithm::EdDsa),
+      _ => Err(anyhow::anyhow!("Invalid algorithm")),
+    }
+  }
+}
+
+// the names registered for the JWS `alg` header
+impl From<JwtAlgorithm> for &'static str {
+  fn from(algorithm: JwtAlgorithm) -> Self {
+    match algorithm {
+      JwtAlgorithm::Hs256 => "HS256",
+      JwtAlgorithm::EdDsa => "EdDSA",
+    }
+  }
+}
+
+impl fmt::Display for JwtAlgorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
@@ -1,13 +1,32 @@
 mod b64;
+mod codec;
 mod csv;
+mod escape;
 mod genpass;
 mod http;
+mod jwt;
+mod otp;
 mod text;
 
-pub use self::b64::{Base64DecodeOpts, Base64EncodeOpts, Base64SubCommand};
-pub use self::csv::{CsvOpts, OutputFormat};
-pub use self::genpass::GenPassOpts;
+pub use self::b64::{Base64DecodeOpts, Base64EncodeOpts, Base64Format, Base64SubCommand};
+pub use self::codec::{Codec, DecodeOpts, EncodeOpts};
+pub use self::csv::{
+  CsvCompression, CsvFmtOpts, CsvMaskOpts, CsvOpts, CsvQuoteStyle, CsvSubCommand, LineEnding,
+  MaskRule, MaskStrategy, OutputFormat,
+};
+pub use self::escape::{
+  HtmlEscapeOpts, HtmlSubCommand, HtmlUnescapeOpts, QpDecodeOpts, QpEncodeOpts, QpSubCommand,
+  UrlDecodeOpts, UrlEncodeOpts, UrlSubCommand,
+};
+pub use self::genpass::{
+  BreachHashFormat, GenPassCheckOpts, GenPassFormat, GenPassOpts, GenPassPinOpts,
+  GenPassSubCommand, GenPassTokenOpts, TokenKind,
+};
 pub use self::http::{HttpServeOpts, HttpSubCommand};
+pub use self::jwt::{JwtAlgorithm, JwtDecodeOpts, JwtSignOpts, JwtSubCommand, JwtVerifyOpts};
+pub use self::otp::{
+  OtpAlgorithm, OtpCodeOpts, OtpGenerateOpts, OtpParams, OtpSecret, OtpSubCommand, OtpVerifyOpts,
+};
 pub use self::text::{
   DecryptOpts, EncryptOpts, TextKeyGenerateOpts, TextSignFormat, TextSignOpts, TextSubCommand,
   TextVerifyOpts,
@@ -35,11 +54,38 @@ pub enum SubCommand {
   #[command(subcommand, about = "Base64 encode/decode")]
   Base64(Base64SubCommand),
 
+  #[command(
+    name = "encode",
+    about = "Encode with hex, base32, base58, base85 and other codecs"
+  )]
+  Encode(EncodeOpts),
+
+  #[command(
+    name = "decode",
+    about = "Decode hex, base32, base58, base85 and other codecs"
+  )]
+  Decode(DecodeOpts),
+
+  #[command(subcommand, about = "URL percent-encode/decode")]
+  Url(UrlSubCommand),
+
+  #[command(subcommand, about = "HTML entity escape/unescape")]
+  Html(HtmlSubCommand),
+
+  #[command(subcommand, about = "Quoted-printable encode/decode")]
+  Qp(QpSubCommand),
+
   #[command(subcommand, about = "Text sign/verify")]
   Text(TextSubCommand),
 
   #[command(subcommand, about = "HTTP server")]
   Http(HttpSubCommand),
+
+  #[command(subcommand, about = "TOTP/HOTP one-time passwords")]
+  Otp(OtpSubCommand),
+
+  #[command(subcommand, about = "JWT decode/sign/verify")]
+  Jwt(JwtSubCommand),
 }
 
 pub fn verify_input_file(file_name: &str) -> Result<String, &'static str> {
@@ -62,14 +108,14 @@ pub fn verify_path(path: &str) -> Result<PathBuf, &'static str> {
 /// 验证输出路径：确保父目录存在且可写
 fn verify_output_path(path: &str) -> Result<PathBuf, anyhow::Error> {
   let path = Path::new(path);
-  if let Some(parent) = path.parent() {
-    if !parent.as_os_str().is_empty() {
-      if !parent.exists() {
-        return Err(anyhow::anyhow!("Parent directory does not exist"));
-      }
-      if !parent.is_dir() {
-        return Err(anyhow::anyhow!("Parent path is not a directory"));
-      }
+  if let Some(parent) = path.parent()
+    && !parent.as_os_str().is_empty()
+  {
+    if !parent.exists() {
+      return Err(anyhow::anyhow!("Parent directory does not exist"));
+    }
+    if !parent.is_dir() {
+      return Err(anyhow::anyhow!("Parent path is not a directory"));
     }
   }
   Ok(path.to_path_buf())
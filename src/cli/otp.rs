@@ -0,0 +1,254 @@
+use super::verify_input_file;
+use crate::{
+  CmdExecutor, OtpOptions, decode_otp_secret, get_content, hotp, otpauth_uri, process_otp_secret,
+  totp, unix_time, verify_hotp, verify_totp,
+};
+use clap::{Args, Parser};
+use enum_dispatch::enum_dispatch;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum OtpSubCommand {
+  #[command(about = "Generate a base32 secret and its otpauth:// URI")]
+  Generate(OtpGenerateOpts),
+
+  #[command(about = "Compute the current TOTP code, or a HOTP code with --counter")]
+  Code(OtpCodeOpts),
+
+  #[command(about = "Verify a TOTP/HOTP code")]
+  Verify(OtpVerifyOpts),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OtpAlgorithm {
+  Sha1,
+  Sha256,
+  Sha512,
+}
+
+#[derive(Debug, Args)]
+pub struct OtpParams {
+  #[arg(long, value_parser = parse_algorithm, default_value = "sha1")]
+  pub algorithm: OtpAlgorithm,
+
+  #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(6..=8))]
+  pub digits: u32,
+
+  /// TOTP time step in seconds
+  #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+  pub period: u64,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct OtpSecret {
+  /// Base32 secret
+  #[arg(short, long)]
+  pub secret: Option<String>,
+
+  /// File holding the base32 secret, keeps it out of the shell history
+  #[arg(long, value_parser = verify_input_file)]
+  pub secret_file: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpGenerateOpts {
+  #[arg(long, default_value = "rcli")]
+  pub issuer: String,
+
+  #[arg(long, default_value = "user")]
+  pub account: String,
+
+  /// Secret size in bytes
+  #[arg(long, default_value_t = 20)]
+  pub bytes: usize,
+
+  /// Emit a HOTP URI starting at this counter instead of a TOTP one
+  #[arg(long)]
+  pub counter: Option<u64>,
+
+  #[command(flatten)]
+  pub params: OtpParams,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpCodeOpts {
+  #[command(flatten)]
+  pub secret: OtpSecret,
+
+  /// Compute a HOTP code for this counter instead of a TOTP code
+  #[arg(long)]
+  pub counter: Option<u64>,
+
+  /// Unix time to compute the TOTP code for, defaults to now
+  #[arg(long, conflicts_with = "counter")]
+  pub time: Option<u64>,
+
+  #[command(flatten)]
+  pub params: OtpParams,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpVerifyOpts {
+  #[command(flatten)]
+  pub secret: OtpSecret,
+
+  #[arg(short, long)]
+  pub code: String,
+
+  /// Verify a HOTP code starting at this counter instead of a TOTP code
+  #[arg(long)]
+  pub counter: Option<u64>,
+
+  /// Accepted time steps (TOTP) or look-ahead counters (HOTP) around the expected one
+  #[arg(long, default_value_t = 1)]
+  pub skew: u64,
+
+  /// Unix time to verify the TOTP code at, defaults to now
+  #[arg(long, conflicts_with = "counter")]
+  pub time: Option<u64>,
+
+  #[command(flatten)]
+  pub params: OtpParams,
+}
+
+impl CmdExecutor for OtpGenerateOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    if self.bytes < 16 {
+      anyhow::bail!("Secrets shorter than 16 bytes are not allowed by RFC 4226");
+    }
+    let secret = process_otp_secret(self.bytes);
+    let uri = otpauth_uri(
+      &secret,
+      &self.issuer,
+      &self.account,
+      self.counter,
+      self.params.options(),
+    );
+    println!("{}", secret);
+    println!("{}", uri);
+    Ok(())
+  }
+}
+
+impl CmdExecutor for OtpCodeOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let secret = self.secret.load()?;
+    let options = self.params.options();
+    let code = match self.counter {
+      Some(counter) => hotp(&secret, counter, options)?,
+      None => {
+        let time = self.time.map_or_else(unix_time, Ok)?;
+        let code = totp(&secret, time, options)?;
+        let remaining = options.period - time % options.period;
+        eprintln!("Valid for {}s", remaining);
+        code
+      }
+    };
+    println!("{}", code);
+    Ok(())
+  }
+}
+
+impl CmdExecutor for OtpVerifyOpts {
+  async fn execute(self) -> anyhow::Result<()> {
+    let secret = self.secret.load()?;
+    let options = self.params.options();
+    let code = self.code.trim();
+    match self.counter {
+      Some(counter) => match verify_hotp(&secret, code, counter, self.skew, options)? {
+        Some(matched) => println!("Code verified, next counter is {}", matched + 1),
+        None => anyhow::bail!("Code not verified"),
+      },
+      None => {
+        let time = self.time.map_or_else(unix_time, Ok)?;
+        match verify_totp(&secret, code, time, self.skew, options)? {
+          Some(offset) => println!("Code verified, time step offset {}", offset),
+          None => anyhow::bail!("Code not verified"),
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl OtpParams {
+  fn options(&self) -> OtpOptions {
+    OtpOptions {
+      algorithm: self.algorithm,
+      digits: self.digits,
+      period: self.period,
+    }
+  }
+}
+
+impl OtpSecret {
+  fn load(&self) -> anyhow::Result<Vec<u8>> {
+    match (&self.secret, &self.secret_file) {
+      (Some(secret), _) => decode_otp_secret(secret),
+      (None, Some(path)) => decode_otp_secret(&String::from_utf8(get_content(path)?)?),
+      (None, None) => anyhow::bail!("Missing OTP secret"),
+    }
+  }
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<OtpAlgorithm, anyhow::Error> {
+  algorithm.parse()
+}
+
+impl FromStr for OtpAlgorithm {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "sha1" => Ok(OtpAlgorithm::Sha1),
+      "sha256" => Ok(OtpAlgorithm::Sha256),
+      "sha512" => Ok(OtpAlgorithm::Sha512),
+      _ => Err(anyhow::anyhow!("Invalid algorithm")),
+    }
+  }
+}
+
+impl From<OtpAlgorithm> for &'static str {
+  fn from(algorithm: OtpAlgorithm) -> Self {
+    match algorithm {
+      OtpAlgorithm::Sha1 => "sha1",
+      OtpAlgorithm::Sha256 => "sha256",
+      OtpAlgorithm::Sha512 => "sha512",
+    }
+  }
+}
+
+impl fmt::Display for OtpAlgorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Into::<&'static str>::into(*self))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Opts;
+  use clap::Parser;
+
+  fn parse(args: &[&str]) -> Result<Opts, clap::Error> {
+    Opts::try_parse_from(
+      ["rcli", "otp", "code", "-s", "JBSWY3DPEHPK3PXP"]
+        .iter()
+        .chain(args),
+    )
+  }
+
+  #[test]
+  fn test_otp_params_range() {
+    assert!(parse(&["--period", "60", "--digits", "8"]).is_ok());
+    for args in [
+      &["--period", "0"][..],
+      &["--digits", "5"],
+      &["--digits", "9"],
+    ] {
+      assert!(parse(args).is_err(), "{:?} should be rejected", args);
+    }
+  }
+}
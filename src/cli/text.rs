@@ -178,6 +178,8 @@ impl CmdExecutor for DecryptOpts {
 pub enum TextSignFormat {
   Blake3,
   Ed25519,
+  // Ed25519 over a SHA-512 prehash of the input (RFC 8032), streams large files
+  Ed25519ph,
 }
 
 fn parse_format(format: &str) -> Result<TextSignFormat, anyhow::Error> {
@@ -191,6 +193,7 @@ impl FromStr for TextSignFormat {
     match s {
       "blake3" => Ok(TextSignFormat::Blake3),
       "ed25519" => Ok(TextSignFormat::Ed25519),
+      "ed25519ph" => Ok(TextSignFormat::Ed25519ph),
       _ => Err(anyhow::anyhow!("Invalid format")),
     }
   }
@@ -201,6 +204,7 @@ impl From<TextSignFormat> for &'static str {
     match format {
       TextSignFormat::Blake3 => "blake3",
       TextSignFormat::Ed25519 => "ed25519",
+      TextSignFormat::Ed25519ph => "ed25519ph",
     }
   }
 }
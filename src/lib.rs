@@ -3,19 +3,56 @@ mod process;
 mod utils;
 
 pub use cli::{
-  Base64DecodeOpts, Base64EncodeOpts, CsvOpts, DecryptOpts, EncryptOpts, GenPassOpts,
-  HttpServeOpts, Opts, TextKeyGenerateOpts, TextSignOpts, TextVerifyOpts,
+  Base64DecodeOpts, Base64EncodeOpts, CsvFmtOpts, CsvMaskOpts, CsvOpts, DecodeOpts, DecryptOpts,
+  EncodeOpts, EncryptOpts, GenPassCheckOpts, GenPassOpts, GenPassPinOpts, GenPassTokenOpts,
+  HtmlEscapeOpts, HtmlUnescapeOpts, HttpServeOpts, JwtDecodeOpts, JwtSignOpts, JwtVerifyOpts, Opts,
+  OtpCodeOpts, OtpGenerateOpts, OtpVerifyOpts, QpDecodeOpts, QpEncodeOpts, TextKeyGenerateOpts,
+  TextSignOpts, TextVerifyOpts, UrlDecodeOpts, UrlEncodeOpts,
 };
 pub use cli::{
-  Base64SubCommand, HttpSubCommand, OutputFormat, SubCommand, TextSignFormat, TextSubCommand,
+  Base64Format, Base64SubCommand, Codec, CsvCompression, CsvQuoteStyle, CsvSubCommand,
+  GenPassFormat, GenPassSubCommand, HtmlSubCommand, HttpSubCommand, JwtAlgorithm, JwtSubCommand,
+  LineEnding, MaskRule, MaskStrategy, OtpAlgorithm, OtpParams, OtpSecret, OtpSubCommand,
+  OutputFormat, QpSubCommand, SubCommand, TextSignFormat, TextSubCommand, TokenKind, UrlSubCommand,
 };
 use enum_dispatch::enum_dispatch;
-pub use process::process_csv;
-pub use process::process_genpass;
 pub use process::process_http_serve;
+pub use process::{BreachList, CrackTimes, StrengthReport, process_check_passwords};
+pub use process::{
+  ColumnarOptions, CsvRecords, DEFAULT_ROW_GROUP_SIZE, deserialize_records, process_csv,
+  process_csv_with_options, read_records, write_columnar, write_records,
+};
+pub use process::{CsvFmtOptions, process_csv_fmt, process_csv_mask};
+pub use process::{
+  DataUri, process_decode_percent, process_encode_data_uri, read_data_uri_header, sniff_mime,
+};
+pub use process::{
+  DecodedJwt, JwtValidation, TimeClaim, jwt_claims, jwt_time_claims, process_jwt_decode,
+  process_jwt_sign, process_jwt_verify,
+};
+pub use process::{
+  GenPassOptions, Passphrase, PassphraseOptions, PasswordReport, genpass_entropy, genpass_rng,
+  password_report, process_genpass, process_genpass_with_rng, process_passphrase,
+  process_passphrase_with_rng,
+};
+pub use process::{
+  OtpOptions, decode_otp_secret, hotp, otpauth_uri, process_otp_secret, totp, verify_hotp,
+  verify_totp,
+};
+pub use process::{PasswordPolicy, process_genpass_with_policy};
 pub use process::{decrypt_text, encrypt_text, process_generate, process_sign, process_verify};
-pub use process::{process_decode, process_encode};
-pub use utils::{get_content, get_reader};
+pub use process::{hexdump, process_decode, process_decode_lenient, process_encode};
+pub use process::{pin_weakness, process_pattern, process_pin, process_pronounceable};
+pub use process::{process_codec_decode, process_codec_encode};
+pub use process::{
+  process_html_escape, process_html_unescape, process_qp_decode, process_qp_encode,
+  process_url_decode, process_url_encode,
+};
+pub use process::{process_token, verify_api_key};
+pub use utils::{
+  get_content, get_reader, get_writer, unix_time, unix_time_millis, write_decoded,
+  write_secret_file,
+};
 
 #[allow(async_fn_in_trait)]
 #[enum_dispatch]
@@ -1,20 +1,327 @@
-use anyhow::Result;
-use base64::{Engine as _, engine::general_purpose::URL_SAFE};
-use std::io::Read;
+use anyhow::{Result, bail};
+use base64::{
+  alphabet,
+  engine::{
+    DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig, general_purpose::STANDARD_NO_PAD,
+  },
+  read::DecoderReader,
+  write::EncoderWriter,
+};
+use percent_encoding::percent_decode;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
 
-pub fn process_encode(reader: &mut dyn Read) -> Result<String> {
+use crate::cli::Base64Format;
+
+// enough for every magic number below
+const SNIFF_LEN: u64 = 512;
+
+/// Header of a `data:` URI, see RFC 2397.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUri {
+  pub mime: String,
+  pub base64: bool,
+}
+
+/// Stream `reader` to `writer` as base64, in constant memory.
+///
+/// With `wrap` the output is broken into lines of that many characters, e.g.
+/// 76 for MIME or 64 for PEM, each ending with a newline.
+pub fn process_encode(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  format: Base64Format,
+  padding: bool,
+  wrap: Option<usize>,
+) -> Result<()> {
+  let engine = engine(format, padding);
+  match wrap {
+    Some(0) => bail!("Wrap width must be greater than 0"),
+    Some(width) => {
+      let mut encoder = EncoderWriter::new(LineWrap::new(writer, width), &engine);
+      io::copy(reader, &mut encoder)?;
+      encoder.finish()?.finish()?;
+    }
+    None => {
+      let mut encoder = EncoderWriter::new(writer, &engine);
+      io::copy(reader, &mut encoder)?;
+      encoder.finish()?.flush()?;
+    }
+  }
+  Ok(())
+}
+
+/// Encode `reader` as a `data:<mime>;base64,...` URI, sniffing the MIME type
+/// from the leading bytes and falling back to the extension of `path`.
+///
+/// Returns the detected MIME type.
+pub fn process_encode_data_uri(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  path: Option<&str>,
+) -> Result<&'static str> {
+  let mut head = Vec::new();
+  reader.take(SNIFF_LEN).read_to_end(&mut head)?;
+  let mime = sniff_mime(&head, path);
+  write!(writer, "data:{};base64,", mime)?;
+  process_encode(
+    &mut head.as_slice().chain(reader),
+    writer,
+    Base64Format::Standard,
+    true,
+    None,
+  )?;
+  Ok(mime)
+}
+
+/// Consume the `data:...,` header when the input is a data URI, leaving the
+/// reader at the start of the payload.
+pub fn read_data_uri_header(reader: &mut dyn BufRead) -> Result<Option<DataUri>> {
+  let buf = reader.fill_buf()?;
+  let skip = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+  if !buf[skip..].starts_with(b"data:") {
+    return Ok(None);
+  }
+  let mut header = Vec::new();
+  reader.read_until(b',', &mut header)?;
+  if header.pop() != Some(b',') {
+    bail!("Invalid data URI: missing ','");
+  }
+  let header = std::str::from_utf8(&header[skip + 5..])?;
+  let mut params = header.split(';');
+  let mime = match params.next() {
+    Some(mime) if !mime.is_empty() => mime.to_string(),
+    // RFC 2397 default
+    _ => "text/plain".to_string(),
+  };
+  let base64 = params.any(|p| p.eq_ignore_ascii_case("base64"));
+  Ok(Some(DataUri { mime, base64 }))
+}
+
+/// Decode the percent-encoded payload of a non base64 data URI.
+pub fn process_decode_percent(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
   let mut buf = Vec::new();
   reader.read_to_end(&mut buf)?;
-  let encoded = URL_SAFE.encode(buf);
-  Ok(encoded)
+  let decoded: Vec<u8> = percent_decode(buf.trim_ascii()).collect();
+  writer.write_all(&decoded)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Guess the MIME type of data from its leading bytes, then from the file extension.
+pub fn sniff_mime(head: &[u8], path: Option<&str>) -> &'static str {
+  const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x00asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"OggS", "audio/ogg"),
+    (b"ID3", "audio/mpeg"),
+  ];
+  if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+    return mime;
+  }
+  if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+    return "image/webp";
+  }
+  if head.len() >= 8 && &head[4..8] == b"ftyp" {
+    return "video/mp4";
+  }
+  let ext = path
+    .and_then(|p| Path::new(p).extension())
+    .and_then(|e| e.to_str())
+    .map(|e| e.to_ascii_lowercase());
+  let by_ext = match ext.as_deref() {
+    Some("svg") => Some("image/svg+xml"),
+    Some("html" | "htm") => Some("text/html"),
+    Some("css") => Some("text/css"),
+    Some("js" | "mjs") => Some("text/javascript"),
+    Some("json") => Some("application/json"),
+    Some("xml") => Some("application/xml"),
+    Some("csv") => Some("text/csv"),
+    Some("md") => Some("text/markdown"),
+    Some("txt") => Some("text/plain"),
+    _ => None,
+  };
+  if let Some(mime) = by_ext {
+    return mime;
+  }
+  let text = match std::str::from_utf8(head) {
+    Ok(text) => text,
+    // a multi-byte char cut off by the sniff window is still text
+    Err(e) if e.error_len().is_none() => {
+      std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or("")
+    }
+    Err(_) => return "application/octet-stream",
+  };
+  if text.trim_start().starts_with("<svg")
+    || (text.trim_start().starts_with("<?xml") && text.contains("<svg"))
+  {
+    "image/svg+xml"
+  } else {
+    "text/plain"
+  }
 }
 
-pub fn process_decode(reader: &mut dyn Read) -> Result<Vec<u8>> {
-  let mut buf = String::new();
-  reader.read_to_string(&mut buf)?;
-  let buf = buf.trim();
-  let decoded = URL_SAFE.decode(buf)?;
-  Ok(decoded)
+/// Stream-decode strictly with the given alphabet and padding, ignoring
+/// whitespace and line breaks.
+pub fn process_decode(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  format: Base64Format,
+  padding: bool,
+) -> Result<()> {
+  let engine = engine(format, padding);
+  let mut decoder = DecoderReader::new(Base64Filter::new(reader, false), &engine);
+  io::copy(&mut decoder, writer)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Stream-decode any base64 variant: standard or url safe alphabet (even mixed),
+/// with or without padding, ignoring whitespace and line breaks.
+pub fn process_decode_lenient(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+  let mut decoder = DecoderReader::new(Base64Filter::new(reader, true), &STANDARD_NO_PAD);
+  io::copy(&mut decoder, writer)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Drops whitespace from base64 text as it is read. In lenient mode padding
+/// is dropped too and the url safe alphabet is mapped to the standard one.
+struct Base64Filter<R> {
+  inner: R,
+  lenient: bool,
+}
+
+impl<R: Read> Base64Filter<R> {
+  fn new(inner: R, lenient: bool) -> Self {
+    Self { inner, lenient }
+  }
+}
+
+impl<R: Read> Read for Base64Filter<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+      let n = self.inner.read(buf)?;
+      if n == 0 {
+        return Ok(0);
+      }
+      let mut len = 0;
+      for i in 0..n {
+        let b = match buf[i] {
+          b if b.is_ascii_whitespace() => continue,
+          b'=' if self.lenient => continue,
+          b'-' if self.lenient => b'+',
+          b'_' if self.lenient => b'/',
+          b => b,
+        };
+        buf[len] = b;
+        len += 1;
+      }
+      // a chunk of pure whitespace is not the end of the input, keep reading
+      if len > 0 {
+        return Ok(len);
+      }
+    }
+  }
+}
+
+/// Inserts a newline every `width` bytes, for wrapped base64 output.
+struct LineWrap<W> {
+  inner: W,
+  width: usize,
+  column: usize,
+}
+
+impl<W: Write> LineWrap<W> {
+  fn new(inner: W, width: usize) -> Self {
+    Self {
+      inner,
+      width,
+      column: 0,
+    }
+  }
+
+  /// Terminate the last line and flush.
+  fn finish(mut self) -> io::Result<()> {
+    if self.column > 0 {
+      self.inner.write_all(b"\n")?;
+    }
+    self.inner.flush()
+  }
+}
+
+impl<W: Write> Write for LineWrap<W> {
+  // always takes the whole buffer, EncoderWriter reports short writes as `Ok(0)`
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut rest = buf;
+    while !rest.is_empty() {
+      let len = rest.len().min(self.width - self.column);
+      self.inner.write_all(&rest[..len])?;
+      self.column += len;
+      if self.column == self.width {
+        self.inner.write_all(b"\n")?;
+        self.column = 0;
+      }
+      rest = &rest[len..];
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+/// Render bytes as a canonical `hexdump -C` style listing.
+pub fn hexdump(data: &[u8]) -> String {
+  let mut ret = String::new();
+  for (i, chunk) in data.chunks(16).enumerate() {
+    let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+    let (left, right) = hex.split_at(hex.len().min(8));
+    let ascii: String = chunk
+      .iter()
+      .map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+          b as char
+        } else {
+          '.'
+        }
+      })
+      .collect();
+    ret.push_str(&format!(
+      "{:08x}  {:<23}  {:<23}  |{}|\n",
+      i * 16,
+      left.join(" "),
+      right.join(" "),
+      ascii
+    ));
+  }
+  ret.push_str(&format!("{:08x}\n", data.len()));
+  ret
+}
+
+fn engine(format: Base64Format, padding: bool) -> GeneralPurpose {
+  let alphabet = match format {
+    Base64Format::Standard => &alphabet::STANDARD,
+    Base64Format::UrlSafe => &alphabet::URL_SAFE,
+  };
+  let config = GeneralPurposeConfig::new()
+    .with_encode_padding(padding)
+    .with_decode_padding_mode(if padding {
+      DecodePaddingMode::RequireCanonical
+    } else {
+      DecodePaddingMode::RequireNone
+    });
+  GeneralPurpose::new(alphabet, config)
 }
 
 #[cfg(test)]
@@ -22,11 +329,28 @@ mod tests {
   use super::*;
   use crate::get_reader;
 
+  fn encode(data: &[u8], format: Base64Format, padding: bool) -> Result<String> {
+    let mut buf = Vec::new();
+    process_encode(&mut &data[..], &mut buf, format, padding, None)?;
+    Ok(String::from_utf8(buf)?)
+  }
+
+  fn decode(data: &str, format: Option<Base64Format>, padding: bool) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    match format {
+      Some(format) => process_decode(&mut data.as_bytes(), &mut buf, format, padding)?,
+      None => process_decode_lenient(&mut data.as_bytes(), &mut buf)?,
+    }
+    Ok(buf)
+  }
+
   #[test]
   fn test_process_encode() -> Result<()> {
     let input = "Cargo.toml";
     let mut reader = get_reader(input)?;
-    assert!(process_encode(&mut reader).is_ok());
+    let mut buf = Vec::new();
+    process_encode(&mut reader, &mut buf, Base64Format::Standard, true, None)?;
+    assert!(!buf.is_empty());
     Ok(())
   }
 
@@ -34,7 +358,133 @@ mod tests {
   fn test_process_decode() -> Result<()> {
     let input = "fuxtures/b64.txt";
     let mut reader = get_reader(input)?;
-    assert!(process_decode(&mut reader).is_ok());
+    let mut buf = Vec::new();
+    process_decode(&mut reader, &mut buf, Base64Format::UrlSafe, true)?;
+    assert!(buf.starts_with(b"[package]"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_formats() -> Result<()> {
+    let data: &[u8] = &[0xfb, 0xff, 0xbf, 0x01];
+    let cases = [
+      (Base64Format::Standard, true, "+/+/AQ=="),
+      (Base64Format::Standard, false, "+/+/AQ"),
+      (Base64Format::UrlSafe, true, "-_-_AQ=="),
+      (Base64Format::UrlSafe, false, "-_-_AQ"),
+    ];
+    for (format, padding, expected) in cases {
+      assert_eq!(encode(data, format, padding)?, expected);
+      assert_eq!(decode(expected, Some(format), padding)?, data);
+      assert_eq!(decode(expected, None, false)?, data);
+    }
+    assert!(decode("+/+/AQ", Some(Base64Format::UrlSafe), false).is_err());
+    assert_eq!(decode("+/-_\nAQ=", None, false)?, data);
+    Ok(())
+  }
+
+  #[test]
+  fn test_streaming_roundtrip() -> Result<()> {
+    // larger than the internal buffers so data crosses chunk boundaries
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let encoded = encode(&data, Base64Format::Standard, true)?;
+    let wrapped: String = encoded
+      .as_bytes()
+      .chunks(76)
+      .map(|line| format!("{}\r\n", std::str::from_utf8(line).unwrap()))
+      .collect();
+    assert_eq!(decode(&wrapped, Some(Base64Format::Standard), true)?, data);
+    assert_eq!(decode(&wrapped, None, false)?, data);
+    Ok(())
+  }
+
+  #[test]
+  fn test_wrap() -> Result<()> {
+    let data = [0u8; 100];
+    let mut buf = Vec::new();
+    process_encode(
+      &mut &data[..],
+      &mut buf,
+      Base64Format::Standard,
+      true,
+      Some(64),
+    )?;
+    let text = String::from_utf8(buf)?;
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[..2].iter().all(|l| l.len() == 64));
+    assert_eq!(lines[2], "AAAAAA==");
+    assert!(text.ends_with("=\n"));
+    assert_eq!(decode(&text, Some(Base64Format::Standard), true)?, data);
+
+    let mut buf = Vec::new();
+    process_encode(
+      &mut &data[..96],
+      &mut buf,
+      Base64Format::Standard,
+      true,
+      Some(64),
+    )?;
+    assert_eq!(buf.len(), 128 + 2);
     Ok(())
   }
+
+  #[test]
+  fn test_data_uri() -> Result<()> {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    let mut buf = Vec::new();
+    let mime = process_encode_data_uri(&mut &png[..], &mut buf, None)?;
+    assert_eq!(mime, "image/png");
+    let uri = String::from_utf8(buf)?;
+    assert_eq!(uri, "data:image/png;base64,iVBORw0KGgoAAAANSUhEUg==");
+
+    let mut reader = uri.as_bytes();
+    let header = read_data_uri_header(&mut reader)?;
+    assert_eq!(
+      header,
+      Some(DataUri {
+        mime: "image/png".into(),
+        base64: true
+      })
+    );
+    let mut out = Vec::new();
+    process_decode_lenient(&mut reader, &mut out)?;
+    assert_eq!(out, png);
+
+    let mut reader = "data:,Hello%2C%20World".as_bytes();
+    let header = read_data_uri_header(&mut reader)?.unwrap();
+    assert_eq!(header.mime, "text/plain");
+    assert!(!header.base64);
+    let mut out = Vec::new();
+    process_decode_percent(&mut reader, &mut out)?;
+    assert_eq!(out, b"Hello, World");
+
+    assert_eq!(read_data_uri_header(&mut "aGVsbG8=".as_bytes())?, None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_sniff_mime() {
+    assert_eq!(sniff_mime(b"GIF89a...", None), "image/gif");
+    assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 ", None), "image/webp");
+    assert_eq!(sniff_mime(b"{}", Some("a.json")), "application/json");
+    assert_eq!(sniff_mime(b"<svg xmlns=", None), "image/svg+xml");
+    assert_eq!(sniff_mime("héllo".as_bytes(), None), "text/plain");
+    assert_eq!(sniff_mime(b"\xc3", None), "text/plain");
+    assert_eq!(
+      sniff_mime(b"\xff\xfe\x00", None),
+      "application/octet-stream"
+    );
+  }
+
+  #[test]
+  fn test_hexdump() {
+    let dump = hexdump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\x01");
+    assert_eq!(
+      dump,
+      "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+       00000010  00 01                                             |..|\n\
+       00000012\n"
+    );
+  }
 }
@@ -0,0 +1,364 @@
+use anyhow::{Result, anyhow, bail};
+use data_encoding::{BASE32, BASE32_NOPAD, Encoding, HEXLOWER, HEXLOWER_PERMISSIVE, Specification};
+use std::io::{Read, Write};
+
+use super::b64::{hexdump, process_decode_lenient, process_encode};
+use crate::cli::{Base64Format, Codec};
+
+const CROCKFORD: &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const Z85: &[u8] =
+  b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+// most bytes a single `*` line of a hexdump may expand to
+const MAX_HEXDUMP_REPEAT: usize = 64 * 1024 * 1024;
+
+/// Encode `reader` to `writer` with the given codec.
+pub fn process_codec_encode(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  codec: Codec,
+) -> Result<()> {
+  // base64 streams, every other codec works on the whole input
+  match codec {
+    Codec::Base64 => return process_encode(reader, writer, Base64Format::Standard, true, None),
+    Codec::Base64Url => return process_encode(reader, writer, Base64Format::UrlSafe, true, None),
+    _ => {}
+  }
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  let encoded = match codec {
+    Codec::Hex => HEXLOWER.encode(&data),
+    Codec::Hexdump => hexdump(&data),
+    Codec::Base32 => BASE32.encode(&data),
+    Codec::Crockford => crockford()?.encode(&data),
+    Codec::Base58 => bs58::encode(&data).into_string(),
+    Codec::Base58Check => bs58::encode(&data).with_check().into_string(),
+    Codec::Ascii85 => ascii85_encode(&data),
+    Codec::Z85 => z85_encode(&data)?,
+    Codec::Base64 | Codec::Base64Url => unreachable!("handled above"),
+  };
+  writer.write_all(encoded.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Decode `reader` to `writer` with the given codec. Whitespace and line
+/// breaks in the input are ignored.
+pub fn process_codec_decode(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  codec: Codec,
+) -> Result<()> {
+  if let Codec::Base64 | Codec::Base64Url = codec {
+    return process_decode_lenient(reader, writer);
+  }
+  let mut input = String::new();
+  reader.read_to_string(&mut input)?;
+  let decoded = match codec {
+    Codec::Hexdump => hexdump_decode(&input)?,
+    _ => {
+      let input: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+      match codec {
+        Codec::Hex => HEXLOWER_PERMISSIVE.decode(input.as_bytes())?,
+        Codec::Base32 => {
+          BASE32_NOPAD.decode(input.trim_end_matches('=').to_uppercase().as_bytes())?
+        }
+        Codec::Crockford => crockford_decode(&input)?,
+        Codec::Base58 => bs58::decode(&input).into_vec()?,
+        Codec::Base58Check => bs58::decode(&input).with_check(None).into_vec()?,
+        Codec::Ascii85 => ascii85_decode(&input)?,
+        Codec::Z85 => z85_decode(&input)?,
+        Codec::Hexdump | Codec::Base64 | Codec::Base64Url => unreachable!("handled above"),
+      }
+    }
+  };
+  writer.write_all(&decoded)?;
+  writer.flush()?;
+  Ok(())
+}
+
+fn crockford() -> Result<Encoding> {
+  let mut spec = Specification::new();
+  spec.symbols.push_str(CROCKFORD);
+  Ok(spec.encoding()?)
+}
+
+// case insensitive, `I`/`L` read as 1 and `O` as 0, `-` separators ignored
+fn crockford_decode(input: &str) -> Result<Vec<u8>> {
+  let input: String = input
+    .chars()
+    .filter(|c| *c != '-')
+    .map(|c| match c.to_ascii_uppercase() {
+      'I' | 'L' => '1',
+      'O' => '0',
+      c => c,
+    })
+    .collect();
+  Ok(crockford()?.decode(input.as_bytes())?)
+}
+
+// parse a canonical `hexdump -C` listing back to bytes, expanding the `*`
+// lines that stand for repeats of the previous row
+fn hexdump_decode(input: &str) -> Result<Vec<u8>> {
+  let mut ret = Vec::new();
+  let mut prev: Vec<u8> = Vec::new();
+  let mut repeat = false;
+  for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+    if line == "*" {
+      if prev.len() != 16 {
+        bail!("Invalid hexdump: `*` must follow a full line");
+      }
+      repeat = true;
+      continue;
+    }
+    let mut tokens = line.split_whitespace();
+    let token = tokens.next().unwrap_or_default();
+    let offset =
+      usize::from_str_radix(token, 16).map_err(|_| anyhow!("Invalid hexdump offset: {}", token))?;
+    if repeat {
+      if offset < ret.len() || offset % 16 != 0 || (offset - ret.len()) % 16 != 0 {
+        bail!("Invalid hexdump offset {} after `*`", token);
+      }
+      if offset - ret.len() > MAX_HEXDUMP_REPEAT {
+        bail!(
+          "Invalid hexdump offset {} after `*`, repeats more than {} bytes",
+          token,
+          MAX_HEXDUMP_REPEAT
+        );
+      }
+      while ret.len() < offset {
+        ret.extend_from_slice(&prev);
+      }
+      repeat = false;
+    }
+    if offset != ret.len() {
+      bail!(
+        "Invalid hexdump offset {}, expected {:08x}",
+        token,
+        ret.len()
+      );
+    }
+    // the `|...|` column is the ASCII view
+    let mut row = Vec::with_capacity(16);
+    for token in tokens.take_while(|t| !t.starts_with('|')) {
+      if token.len() != 2 {
+        bail!("Invalid hexdump byte: {}", token);
+      }
+      row.push(u8::from_str_radix(token, 16)?);
+    }
+    ret.extend_from_slice(&row);
+    if !row.is_empty() {
+      prev = row;
+    }
+  }
+  if repeat {
+    bail!("Invalid hexdump: `*` must be followed by an offset");
+  }
+  Ok(ret)
+}
+
+// btoa style ascii85: `z` for all zero groups, no `<~ ~>` delimiters
+fn ascii85_encode(data: &[u8]) -> String {
+  let mut ret = String::with_capacity(data.len() * 5 / 4 + 5);
+  for chunk in data.chunks(4) {
+    if chunk == [0, 0, 0, 0] {
+      ret.push('z');
+      continue;
+    }
+    let mut group = [0u8; 4];
+    group[..chunk.len()].copy_from_slice(chunk);
+    let digits = base85_digits(u32::from_be_bytes(group));
+    ret.extend(digits[..chunk.len() + 1].iter().map(|d| (d + b'!') as char));
+  }
+  ret
+}
+
+fn ascii85_decode(input: &str) -> Result<Vec<u8>> {
+  let input = input.strip_prefix("<~").unwrap_or(input);
+  let input = input.strip_suffix("~>").unwrap_or(input);
+  let mut ret = Vec::with_capacity(input.len() * 4 / 5);
+  let mut group = Vec::with_capacity(5);
+  for c in input.bytes() {
+    match c {
+      b'z' if group.is_empty() => ret.extend_from_slice(&[0; 4]),
+      b'!'..=b'u' => {
+        group.push(c - b'!');
+        if group.len() == 5 {
+          ret.extend_from_slice(&base85_value(&group)?.to_be_bytes());
+          group.clear();
+        }
+      }
+      _ => bail!("Invalid ascii85 character: {}", c as char),
+    }
+  }
+  match group.len() {
+    0 => {}
+    1 => bail!("Invalid ascii85 input: dangling final character"),
+    len => {
+      // pad the last group with the highest digit, then drop the padding bytes
+      group.resize(5, 84);
+      ret.extend_from_slice(&base85_value(&group)?.to_be_bytes()[..len - 1]);
+    }
+  }
+  Ok(ret)
+}
+
+// ZeroMQ Z85, the input length must be a multiple of 4
+fn z85_encode(data: &[u8]) -> Result<String> {
+  if !data.len().is_multiple_of(4) {
+    bail!(
+      "Z85 input length must be a multiple of 4, got {}",
+      data.len()
+    );
+  }
+  Ok(
+    data
+      .chunks(4)
+      .flat_map(|chunk| base85_digits(u32::from_be_bytes(chunk.try_into().expect("4 bytes"))))
+      .map(|d| Z85[d as usize] as char)
+      .collect(),
+  )
+}
+
+fn z85_decode(input: &str) -> Result<Vec<u8>> {
+  if !input.len().is_multiple_of(5) {
+    bail!(
+      "Z85 input length must be a multiple of 5, got {}",
+      input.len()
+    );
+  }
+  let digits = input
+    .bytes()
+    .map(|c| {
+      Z85
+        .iter()
+        .position(|&z| z == c)
+        .map(|d| d as u8)
+        .ok_or_else(|| anyhow!("Invalid Z85 character: {}", c as char))
+    })
+    .collect::<Result<Vec<_>>>()?;
+  let mut ret = Vec::with_capacity(digits.len() * 4 / 5);
+  for group in digits.chunks(5) {
+    ret.extend_from_slice(&base85_value(group)?.to_be_bytes());
+  }
+  Ok(ret)
+}
+
+fn base85_digits(mut value: u32) -> [u8; 5] {
+  let mut digits = [0u8; 5];
+  for d in digits.iter_mut().rev() {
+    *d = (value % 85) as u8;
+    value /= 85;
+  }
+  digits
+}
+
+fn base85_value(digits: &[u8]) -> Result<u32> {
+  let value = digits.iter().fold(0u64, |acc, &d| acc * 85 + d as u64);
+  u32::try_from(value).map_err(|_| anyhow!("Invalid base85 group, value out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encode(data: &[u8], codec: Codec) -> Result<String> {
+    let mut buf = Vec::new();
+    process_codec_encode(&mut &data[..], &mut buf, codec)?;
+    Ok(String::from_utf8(buf)?)
+  }
+
+  fn decode(data: &str, codec: Codec) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    process_codec_decode(&mut data.as_bytes(), &mut buf, codec)?;
+    Ok(buf)
+  }
+
+  #[test]
+  fn test_known_vectors() -> Result<()> {
+    let cases: [(Codec, &[u8], &str); 8] = [
+      (Codec::Hex, b"foobar", "666f6f626172"),
+      (Codec::Base32, b"foobar", "MZXW6YTBOI======"),
+      (Codec::Crockford, b"foobar", "CSQPYRK1E8"),
+      (Codec::Base58, b"Hello World!", "2NEpo7TZRRrLZSi2U"),
+      (Codec::Base58Check, &[0u8; 1], "1Wh4bh"),
+      (Codec::Ascii85, b"Man is d", "9jqo^BlbD-"),
+      (
+        Codec::Z85,
+        &[0x86, 0x4f, 0xd2, 0x6f, 0xb5, 0x59, 0xf7, 0x5b],
+        "HelloWorld",
+      ),
+      (Codec::Base64, b"foobar", "Zm9vYmFy"),
+    ];
+    for (codec, data, expected) in cases {
+      assert_eq!(encode(data, codec)?, expected, "{}", codec);
+      assert_eq!(decode(expected, codec)?, data, "{}", codec);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_roundtrip() -> Result<()> {
+    let data: Vec<u8> = (0..=255u8).chain([0, 0, 0, 0, 1, 2]).collect();
+    for codec in [
+      Codec::Hex,
+      Codec::Hexdump,
+      Codec::Base32,
+      Codec::Crockford,
+      Codec::Base58,
+      Codec::Base58Check,
+      Codec::Ascii85,
+      Codec::Base64Url,
+    ] {
+      let encoded = encode(&data, codec)?;
+      assert_eq!(decode(&encoded, codec)?, data, "{}", codec);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_lenient_decode() -> Result<()> {
+    assert_eq!(decode("66 6F\n6f", Codec::Hex)?, b"foo");
+    assert_eq!(decode("mzxw6ytboi", Codec::Base32)?, b"foobar");
+    assert_eq!(decode("csqp-yrk1-e8", Codec::Crockford)?, b"foobar");
+    assert_eq!(decode("CSQPYRKLE8", Codec::Crockford)?, b"foobar");
+    assert_eq!(decode("<~9jqo^\nBlbD-~>", Codec::Ascii85)?, b"Man is d");
+    Ok(())
+  }
+
+  #[test]
+  fn test_hexdump_repeated_lines() -> Result<()> {
+    // as printed by `hexdump -C` for 64 zero bytes followed by "hello"
+    let listing = "\
+00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000040  68 65 6c 6c 6f                                    |hello|
+00000045
+";
+    let mut data = vec![0u8; 64];
+    data.extend_from_slice(b"hello");
+    assert_eq!(decode(listing, Codec::Hexdump)?, data);
+
+    // offsets that skip or overlap data are rejected
+    assert!(decode("00000000  00\n00000010  01\n", Codec::Hexdump).is_err());
+    assert!(decode("00000000  00 01\n00000001  02\n", Codec::Hexdump).is_err());
+    assert!(decode("00000000  00\n*\n00000010\n", Codec::Hexdump).is_err());
+    // a huge or misaligned offset after `*` must not expand
+    let star = &listing[..listing.find('*').unwrap() + 2];
+    assert!(decode(&format!("{star}ffffffffffff0\n"), Codec::Hexdump).is_err());
+    assert!(decode(&format!("{star}00000048\n"), Codec::Hexdump).is_err());
+    let short = "00000000  00\n00000001  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00\n";
+    assert!(decode(&format!("{short}*\n00000021\n"), Codec::Hexdump).is_err());
+    assert!(decode(&listing[..listing.find('*').unwrap() + 2], Codec::Hexdump).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_input() {
+    assert!(decode("1Wh4bi", Codec::Base58Check).is_err());
+    assert!(encode(b"abc", Codec::Z85).is_err());
+    assert!(decode("Hello", Codec::Z85).is_ok());
+    assert!(decode("Hell", Codec::Z85).is_err());
+    assert!(decode("s8W-\"", Codec::Ascii85).is_err());
+    assert!(decode("9jqo^B", Codec::Ascii85).is_err());
+  }
+}
@@ -1,26 +1,422 @@
-use csv::Reader;
+use anyhow::{Result, bail};
+use arrow::csv::reader::Format;
+use arrow::ipc::CompressionType;
+use arrow::ipc::writer::{FileWriter, IpcWriteOptions};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::fs;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
 
-use crate::cli::OutputFormat;
+use crate::cli::{CsvCompression, OutputFormat};
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-  let mut reader = Reader::from_path(input)?;
-  let mut ret = Vec::with_capacity(128);
-  let headers = reader.headers()?.clone();
+// rows per Arrow record batch, independent of the Parquet row group size
+const BATCH_SIZE: usize = 8192;
+/// Rows per Parquet row group unless told otherwise. The writer holds a whole
+/// row group in memory, so this stays well below arrow-rs' own 1M default.
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 64 * 1024;
 
-  for result in reader.records() {
-    let record = result?;
-    let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-    ret.push(json_value);
+/// Tuning knobs for the columnar (Parquet / Arrow IPC) writers.
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnarOptions {
+  /// Rows per Parquet row group.
+  pub row_group_size: usize,
+  pub compression: CsvCompression,
+}
+
+impl Default for ColumnarOptions {
+  fn default() -> Self {
+    Self {
+      row_group_size: DEFAULT_ROW_GROUP_SIZE,
+      compression: CsvCompression::Zstd,
+    }
   }
+}
+
+/// Iterator over the rows of a CSV source, each row converted to a JSON object
+/// keyed by the header names.
+pub struct CsvRecords<R> {
+  reader: Reader<R>,
+  headers: StringRecord,
+}
+
+impl<R: Read> Iterator for CsvRecords<R> {
+  type Item = Result<Value>;
 
+  fn next(&mut self) -> Option<Self::Item> {
+    let mut record = StringRecord::new();
+    match self.reader.read_record(&mut record) {
+      Ok(true) => Some(Ok(self.headers.iter().zip(record.iter()).collect())),
+      Ok(false) => None,
+      Err(e) => Some(Err(e.into())),
+    }
+  }
+}
+
+/// Read CSV rows from any reader as `serde_json::Value` objects.
+///
+/// When `has_headers` is false the columns are keyed by their index.
+pub fn read_records<R: Read>(reader: R, delimiter: u8, has_headers: bool) -> Result<CsvRecords<R>> {
+  let mut reader = ReaderBuilder::new()
+    .delimiter(delimiter)
+    .has_headers(has_headers)
+    .from_reader(reader);
+  let headers = if has_headers {
+    reader.headers()?.clone()
+  } else {
+    let len = reader.headers()?.len();
+    (0..len).map(|i| i.to_string()).collect()
+  };
+  Ok(CsvRecords { reader, headers })
+}
+
+/// Read CSV rows from any reader, deserializing each row into `T` by header name.
+pub fn deserialize_records<T, R>(reader: R, delimiter: u8) -> impl Iterator<Item = Result<T>>
+where
+  T: DeserializeOwned,
+  R: Read,
+{
+  ReaderBuilder::new()
+    .delimiter(delimiter)
+    .from_reader(reader)
+    .into_deserialize()
+    .map(|r| r.map_err(Into::into))
+}
+
+/// Serialize records to `writer` in the given format.
+///
+/// TOML has no top-level arrays, so the records are written under a `records` key.
+pub fn write_records<W, S>(writer: &mut W, records: &[S], format: OutputFormat) -> Result<()>
+where
+  W: Write + ?Sized,
+  S: Serialize,
+{
   let content = match format {
-    OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-    OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-    OutputFormat::Toml => toml::to_string_pretty(&ret)?,
+    OutputFormat::Json => serde_json::to_string_pretty(records)?,
+    OutputFormat::Yaml => serde_yaml::to_string(records)?,
+    OutputFormat::Toml => {
+      #[derive(Serialize)]
+      struct Table<'a, S> {
+        records: &'a [S],
+      }
+      toml::to_string_pretty(&Table { records })?
+    }
+    OutputFormat::Parquet | OutputFormat::Arrow => {
+      bail!("{} output needs a typed schema, use write_columnar", format)
+    }
   };
+  writer.write_all(content.as_bytes())?;
+  Ok(())
+}
+
+/// Convert CSV to Parquet or Arrow IPC, inferring column types from the data.
+///
+/// The whole input is scanned once for schema inference, then rewound and
+/// converted in record batches of a fixed size. Without headers the columns
+/// are named `column_1`, `column_2`...
+pub fn write_columnar<R, W>(
+  mut reader: R,
+  writer: W,
+  format: OutputFormat,
+  delimiter: u8,
+  has_headers: bool,
+  options: ColumnarOptions,
+) -> Result<()>
+where
+  R: Read + Seek,
+  W: Write + Send,
+{
+  validate_columnar(format, options)?;
+  let (schema, _) = Format::default()
+    .with_header(has_headers)
+    .with_delimiter(delimiter)
+    .infer_schema(&mut reader, None)?;
+  let schema = Arc::new(schema);
+  reader.seek(SeekFrom::Start(0))?;
+  let batches = arrow::csv::ReaderBuilder::new(schema.clone())
+    .with_header(has_headers)
+    .with_delimiter(delimiter)
+    .with_batch_size(BATCH_SIZE)
+    .build(reader)?;
 
-  fs::write(output, content)?;
+  match format {
+    OutputFormat::Parquet => {
+      let props = WriterProperties::builder()
+        .set_max_row_group_row_count(Some(options.row_group_size))
+        .set_compression(options.compression.into())
+        .build();
+      let mut writer = ArrowWriter::try_new(writer, schema, Some(props))?;
+      for batch in batches {
+        writer.write(&batch?)?;
+      }
+      writer.close()?;
+    }
+    OutputFormat::Arrow => {
+      let compression = arrow_compression(options.compression)?;
+      let ipc_options = IpcWriteOptions::default().try_with_compression(compression)?;
+      let mut writer = FileWriter::try_new_with_options(writer, &schema, ipc_options)?;
+      for batch in batches {
+        writer.write(&batch?)?;
+      }
+      writer.finish()?;
+    }
+    _ => bail!("{} is not a columnar format, use write_records", format),
+  }
   Ok(())
 }
+
+pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
+  process_csv_with_options(
+    input,
+    output,
+    format,
+    b',',
+    true,
+    ColumnarOptions::default(),
+  )
+}
+
+/// Convert the CSV file `input` to `output`, reading it with `delimiter` and,
+/// unless `has_headers` is false, taking the column names from the first row.
+pub fn process_csv_with_options(
+  input: &str,
+  output: String,
+  format: OutputFormat,
+  delimiter: u8,
+  has_headers: bool,
+  options: ColumnarOptions,
+) -> Result<()> {
+  let file = File::open(input)?;
+  match format {
+    OutputFormat::Parquet | OutputFormat::Arrow => {
+      // don't leave an empty output file behind for a rejected combination
+      validate_columnar(format, options)?;
+      write_columnar(
+        file,
+        File::create(output)?,
+        format,
+        delimiter,
+        has_headers,
+        options,
+      )
+    }
+    _ => {
+      let ret = read_records(file, delimiter, has_headers)?.collect::<Result<Vec<_>>>()?;
+      write_records(&mut File::create(output)?, &ret, format)
+    }
+  }
+}
+
+fn validate_columnar(format: OutputFormat, options: ColumnarOptions) -> Result<()> {
+  if options.row_group_size == 0 {
+    bail!("Row group size must be greater than 0");
+  }
+  match format {
+    OutputFormat::Parquet => Ok(()),
+    OutputFormat::Arrow => arrow_compression(options.compression).map(|_| ()),
+    _ => bail!("{} is not a columnar format, use write_records", format),
+  }
+}
+
+fn arrow_compression(compression: CsvCompression) -> Result<Option<CompressionType>> {
+  match compression {
+    CsvCompression::None => Ok(None),
+    CsvCompression::Lz4 => Ok(Some(CompressionType::LZ4_FRAME)),
+    CsvCompression::Zstd => Ok(Some(CompressionType::ZSTD)),
+    c => bail!("Arrow IPC does not support {} compression", c),
+  }
+}
+
+impl From<CsvCompression> for Compression {
+  fn from(compression: CsvCompression) -> Self {
+    match compression {
+      CsvCompression::None => Compression::UNCOMPRESSED,
+      CsvCompression::Snappy => Compression::SNAPPY,
+      CsvCompression::Gzip => Compression::GZIP(GzipLevel::default()),
+      CsvCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+      CsvCompression::Lz4 => Compression::LZ4_RAW,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::Deserialize;
+
+  const DATA: &str = "name,age\njack,28\nannie,22\n";
+
+  #[derive(Debug, Deserialize, PartialEq)]
+  struct Person {
+    name: String,
+    age: u8,
+  }
+
+  #[test]
+  fn test_read_records() -> Result<()> {
+    let ret = read_records(DATA.as_bytes(), b',', true)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(ret.len(), 2);
+    assert_eq!(ret[0]["name"], "jack");
+    assert_eq!(ret[1]["age"], "22");
+    Ok(())
+  }
+
+  #[test]
+  fn test_read_records_without_headers() -> Result<()> {
+    let ret = read_records(DATA.as_bytes(), b',', false)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(ret.len(), 3);
+    assert_eq!(ret[0]["0"], "name");
+    Ok(())
+  }
+
+  #[test]
+  fn test_deserialize_records() -> Result<()> {
+    let ret =
+      deserialize_records::<Person, _>(DATA.as_bytes(), b',').collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+      ret[0],
+      Person {
+        name: "jack".into(),
+        age: 28
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_records() -> Result<()> {
+    let ret = read_records(DATA.as_bytes(), b',', true)?.collect::<Result<Vec<_>>>()?;
+    for format in [OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Toml] {
+      let mut buf = Vec::new();
+      write_records(&mut buf, &ret, format)?;
+      assert!(String::from_utf8(buf)?.contains("annie"));
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_columnar() -> Result<()> {
+    let options = ColumnarOptions {
+      row_group_size: 1,
+      compression: CsvCompression::Zstd,
+    };
+    let mut parquet = Vec::new();
+    write_columnar(
+      std::io::Cursor::new(DATA),
+      &mut parquet,
+      OutputFormat::Parquet,
+      b',',
+      true,
+      options,
+    )?;
+    assert!(parquet.starts_with(b"PAR1"));
+
+    let mut ipc = Vec::new();
+    write_columnar(
+      std::io::Cursor::new(DATA),
+      &mut ipc,
+      OutputFormat::Arrow,
+      b',',
+      true,
+      options,
+    )?;
+    assert!(ipc.starts_with(b"ARROW1"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_columnar_rejects_unsupported_compression() {
+    let options = ColumnarOptions {
+      row_group_size: 16,
+      compression: CsvCompression::Snappy,
+    };
+    let ret = write_columnar(
+      std::io::Cursor::new(DATA),
+      Vec::new(),
+      OutputFormat::Arrow,
+      b',',
+      true,
+      options,
+    );
+    assert!(ret.is_err());
+  }
+
+  #[test]
+  fn test_process_csv_rejects_before_creating_output() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("rcli-csv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("in.csv");
+    let output = dir.join("out.arrow");
+    std::fs::write(&input, DATA)?;
+    let options = ColumnarOptions {
+      row_group_size: 16,
+      compression: CsvCompression::Snappy,
+    };
+    let ret = process_csv_with_options(
+      input.to_str().unwrap(),
+      output.to_string_lossy().into(),
+      OutputFormat::Arrow,
+      b',',
+      true,
+      options,
+    );
+    assert!(ret.is_err());
+    assert!(!output.exists());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_process_csv_delimiter_and_header() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("rcli-csv-dialect-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("in.csv");
+    let output = dir.join("out.json");
+    std::fs::write(&input, "a;b\n1;2\n")?;
+    let convert = |has_headers| -> Result<Value> {
+      process_csv_with_options(
+        input.to_str().unwrap(),
+        output.to_string_lossy().into(),
+        OutputFormat::Json,
+        b';',
+        has_headers,
+        ColumnarOptions::default(),
+      )?;
+      Ok(serde_json::from_slice(&std::fs::read(&output)?)?)
+    };
+    assert_eq!(convert(true)?, serde_json::json!([{ "a": "1", "b": "2" }]));
+    assert_eq!(
+      convert(false)?,
+      serde_json::json!([{ "0": "a", "1": "b" }, { "0": "1", "1": "2" }])
+    );
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_columnar_dialect() -> Result<()> {
+    let options = ColumnarOptions::default();
+    let mut ipc = Vec::new();
+    let data = "1;x\n2;y\n";
+    write_columnar(
+      std::io::Cursor::new(data),
+      &mut ipc,
+      OutputFormat::Arrow,
+      b';',
+      false,
+      options,
+    )?;
+    let reader = arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(ipc), None)?;
+    let schema = reader.schema();
+    let batches = reader.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(schema.fields().len(), 2);
+    assert_eq!(schema.field(0).name(), "column_1");
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+    Ok(())
+  }
+}
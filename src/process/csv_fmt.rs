@@ -0,0 +1,138 @@
+use anyhow::Result;
+use csv::{QuoteStyle, ReaderBuilder, StringRecord, Terminator, WriterBuilder};
+use std::io::{Read, Write};
+
+use crate::cli::{CsvQuoteStyle, LineEnding};
+
+/// Output dialect for `rcli csv fmt`.
+#[derive(Debug, Clone, Copy)]
+pub struct CsvFmtOptions {
+  pub delimiter: u8,
+  pub out_delimiter: u8,
+  pub line_ending: LineEnding,
+  pub quote: CsvQuoteStyle,
+  pub trim: bool,
+}
+
+/// Re-emit CSV from `reader` on `writer` in a canonical dialect.
+///
+/// Line breaks inside quoted fields (CRLF, LF or a lone CR) are rewritten to
+/// the chosen line ending as well, so two files that only differ in line
+/// endings produce identical output. Rows keep their own number of fields.
+pub fn process_csv_fmt(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  options: CsvFmtOptions,
+) -> Result<()> {
+  let mut reader = ReaderBuilder::new()
+    .delimiter(options.delimiter)
+    .has_headers(false)
+    .flexible(true)
+    .from_reader(reader);
+  let mut writer = WriterBuilder::new()
+    .delimiter(options.out_delimiter)
+    .terminator(options.line_ending.into())
+    .quote_style(options.quote.into())
+    .flexible(true)
+    .from_writer(writer);
+  let newline = match options.line_ending {
+    LineEnding::Lf => "\n",
+    LineEnding::Crlf => "\r\n",
+  };
+
+  for result in reader.records() {
+    let record = result?;
+    let fields = record.iter().map(|field| {
+      let field = if options.trim {
+        field.trim_end()
+      } else {
+        field
+      };
+      field
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\n', newline)
+    });
+    writer.write_record(&fields.collect::<StringRecord>())?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+impl From<LineEnding> for Terminator {
+  fn from(line_ending: LineEnding) -> Self {
+    match line_ending {
+      LineEnding::Lf => Terminator::Any(b'\n'),
+      LineEnding::Crlf => Terminator::CRLF,
+    }
+  }
+}
+
+impl From<CsvQuoteStyle> for QuoteStyle {
+  fn from(quote: CsvQuoteStyle) -> Self {
+    match quote {
+      CsvQuoteStyle::Necessary => QuoteStyle::Necessary,
+      CsvQuoteStyle::Always => QuoteStyle::Always,
+      CsvQuoteStyle::NonNumeric => QuoteStyle::NonNumeric,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fmt(input: &str, options: CsvFmtOptions) -> Result<String> {
+    let mut out = Vec::new();
+    process_csv_fmt(&mut input.as_bytes(), &mut out, options)?;
+    Ok(String::from_utf8(out)?)
+  }
+
+  fn options() -> CsvFmtOptions {
+    CsvFmtOptions {
+      delimiter: b',',
+      out_delimiter: b',',
+      line_ending: LineEnding::Lf,
+      quote: CsvQuoteStyle::Necessary,
+      trim: false,
+    }
+  }
+
+  #[test]
+  fn test_fmt_normalizes_line_endings() -> Result<()> {
+    let crlf = fmt("name,note\r\n\"jack\",\"a\r\nb\"\r\n", options())?;
+    let lf = fmt("name,note\njack,\"a\nb\"\n", options())?;
+    assert_eq!(crlf, lf);
+    assert_eq!(lf, "name,note\njack,\"a\nb\"\n");
+    Ok(())
+  }
+
+  #[test]
+  fn test_fmt_lone_cr() -> Result<()> {
+    let ret = fmt("name,note\njack,\"a\rb\"\n", options())?;
+    assert_eq!(ret, "name,note\njack,\"a\nb\"\n");
+    assert!(!ret.contains('\r'));
+    Ok(())
+  }
+
+  #[test]
+  fn test_fmt_ragged_rows() -> Result<()> {
+    let ret = fmt("a,b,c\n1,2\n3,4,5,6\n", options())?;
+    assert_eq!(ret, "a,b,c\n1,2\n3,4,5,6\n");
+    Ok(())
+  }
+
+  #[test]
+  fn test_fmt_dialect() -> Result<()> {
+    let options = CsvFmtOptions {
+      delimiter: b';',
+      out_delimiter: b'\t',
+      line_ending: LineEnding::Crlf,
+      quote: CsvQuoteStyle::Always,
+      trim: true,
+    };
+    let ret = fmt("name;age  \njack  ;28\n", options)?;
+    assert_eq!(ret, "\"name\"\t\"age\"\r\n\"jack\"\t\"28\"\r\n");
+    Ok(())
+  }
+}
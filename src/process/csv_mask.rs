@@ -0,0 +1,166 @@
+use anyhow::{Result, anyhow};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use rand::Rng;
+use rand::rngs::ThreadRng;
+use std::io::{Read, Write};
+
+use super::text::Blake3;
+use crate::cli::{MaskRule, MaskStrategy};
+
+const REDACTED: &str = "***";
+// hex chars kept from the keyed hash, enough to stay collision free for joins
+const HASH_LEN: usize = 32;
+
+/// Rewrite `reader` as CSV on `writer`, applying each rule to its column.
+///
+/// The `hash` strategy needs a 32 byte BLAKE3 key, the same format as the key
+/// used by `rcli text sign --format blake3`, so equal values hash equally across files.
+pub fn process_csv_mask(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  rules: &[MaskRule],
+  key: Option<&[u8]>,
+  delimiter: u8,
+) -> Result<()> {
+  let mut reader = ReaderBuilder::new()
+    .delimiter(delimiter)
+    .from_reader(reader);
+  let mut writer = WriterBuilder::new()
+    .delimiter(delimiter)
+    .from_writer(writer);
+  let headers = reader.headers()?.clone();
+
+  let hasher = key.map(Blake3::try_new).transpose()?;
+  let mut columns = Vec::with_capacity(rules.len());
+  for rule in rules {
+    let index = headers
+      .iter()
+      .position(|h| h == rule.column)
+      .ok_or_else(|| anyhow!("Column {} not found", rule.column))?;
+    if matches!(rule.strategy, MaskStrategy::Hash) && hasher.is_none() {
+      return Err(anyhow!("Hash strategy for {} requires --key", rule.column));
+    }
+    columns.push((index, &rule.strategy));
+  }
+
+  writer.write_record(&headers)?;
+  let mut rng = rand::thread_rng();
+  for result in reader.records() {
+    let record = result?;
+    let mut masked: Vec<String> = record.iter().map(String::from).collect();
+    for (index, strategy) in &columns {
+      if let Some(value) = masked.get_mut(*index) {
+        *value = mask_value(value, strategy, hasher.as_ref(), &mut rng);
+      }
+    }
+    writer.write_record(&StringRecord::from(masked))?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+fn mask_value(
+  value: &str,
+  strategy: &MaskStrategy,
+  hasher: Option<&Blake3>,
+  rng: &mut ThreadRng,
+) -> String {
+  match strategy {
+    MaskStrategy::Hash => match hasher {
+      Some(hasher) => hasher.hash(value.as_bytes()).to_hex()[..HASH_LEN].to_string(),
+      None => REDACTED.to_string(),
+    },
+    MaskStrategy::Redact => REDACTED.to_string(),
+    MaskStrategy::Fake => fake_value(value, rng),
+    MaskStrategy::Truncate(n) => value.chars().take(*n).collect(),
+  }
+}
+
+// keep the shape of the value: digits stay digits, letters keep their case,
+// uncased letters (CJK and other scripts) become CJK ideographs, and only
+// punctuation, whitespace and symbols (separators, '@', '.') are left untouched
+fn fake_value(value: &str, rng: &mut impl Rng) -> String {
+  value
+    .chars()
+    .map(|c| {
+      if c.is_numeric() {
+        rng.gen_range(b'0'..=b'9') as char
+      } else if c.is_uppercase() {
+        rng.gen_range(b'A'..=b'Z') as char
+      } else if c.is_lowercase() {
+        rng.gen_range(b'a'..=b'z') as char
+      } else if c.is_alphabetic() {
+        rng.gen_range('\u{4e00}'..='\u{9fa5}')
+      } else {
+        c
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const KEY: &[u8] = include_bytes!("../../fuxtures/blake3.txt");
+  const DATA: &str = "name,email,phone\njack,jack@example.com,138-0000-1111\n";
+
+  fn mask(rules: &[&str], key: Option<&[u8]>) -> Result<String> {
+    let rules = rules
+      .iter()
+      .map(|r| r.parse())
+      .collect::<Result<Vec<MaskRule>>>()?;
+    let mut out = Vec::new();
+    process_csv_mask(&mut DATA.as_bytes(), &mut out, &rules, key, b',')?;
+    Ok(String::from_utf8(out)?)
+  }
+
+  #[test]
+  fn test_mask_strategies() -> Result<()> {
+    let ret = mask(&["name=truncate:1", "email=redact"], None)?;
+    assert_eq!(ret, "name,email,phone\nj,***,138-0000-1111\n");
+    Ok(())
+  }
+
+  #[test]
+  fn test_mask_hash_is_stable() -> Result<()> {
+    let first = mask(&["email=hash"], Some(KEY))?;
+    let second = mask(&["email=hash"], Some(KEY))?;
+    assert_eq!(first, second);
+    assert!(!first.contains("jack@example.com"));
+    assert!(mask(&["email=hash"], None).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_mask_fake_keeps_format() -> Result<()> {
+    let ret = mask(&["phone=fake"], None)?;
+    let phone = ret.lines().nth(1).unwrap().rsplit(',').next().unwrap();
+    assert_eq!(phone.len(), 13);
+    assert_eq!(&phone[3..4], "-");
+    assert_eq!(&phone[8..9], "-");
+    Ok(())
+  }
+
+  #[test]
+  fn test_mask_fake_non_ascii() {
+    let mut rng = rand::thread_rng();
+    let value = "张伟 José Ｎａｍｅ ４２-١٢";
+    let fake = fake_value(value, &mut rng);
+    assert_eq!(fake.chars().count(), value.chars().count());
+    for (c, f) in value.chars().zip(fake.chars()) {
+      if c.is_alphabetic() || c.is_numeric() {
+        assert!(f.is_ascii_alphanumeric() || ('\u{4e00}'..='\u{9fa5}').contains(&f));
+      } else {
+        assert_eq!(c, f);
+      }
+    }
+    // é and the full-width ４ can never be generated, so they must be gone
+    assert!(!fake.contains('é') && !fake.contains('４'));
+  }
+
+  #[test]
+  fn test_mask_unknown_column() {
+    assert!(mask(&["ssn=redact"], None).is_err());
+  }
+}
@@ -0,0 +1,351 @@
+use anyhow::Result;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode, percent_encode};
+use std::io::{Read, Write};
+
+// RFC 3986 unreserved characters stay as-is in a component
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+// a full URL also keeps the reserved delimiters
+const FULL_URL: &AsciiSet = &COMPONENT
+  .remove(b':')
+  .remove(b'/')
+  .remove(b'?')
+  .remove(b'#')
+  .remove(b'[')
+  .remove(b']')
+  .remove(b'@')
+  .remove(b'!')
+  .remove(b'$')
+  .remove(b'&')
+  .remove(b'\'')
+  .remove(b'(')
+  .remove(b')')
+  .remove(b'*')
+  .remove(b'+')
+  .remove(b',')
+  .remove(b';')
+  .remove(b'=');
+const QP_LINE_LEN: usize = 76;
+
+/// Percent-encode the input as a URL component, or as a full URL keeping the
+/// reserved delimiters with `full`. `form` encodes spaces as `+`. One trailing
+/// line break, as left by `echo` or a text file, is not encoded.
+pub fn process_url_encode(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  full: bool,
+  form: bool,
+) -> Result<()> {
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  let set = if full { FULL_URL } else { COMPONENT };
+  let mut encoded = percent_encode(trim_newline(&data), set).to_string();
+  if form {
+    encoded = encoded.replace("%20", "+");
+  }
+  writer.write_all(encoded.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Decode `%XX` escapes, and `+` as space with `form`.
+pub fn process_url_decode(reader: &mut dyn Read, writer: &mut dyn Write, form: bool) -> Result<()> {
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  if form {
+    data
+      .iter_mut()
+      .filter(|b| **b == b'+')
+      .for_each(|b| *b = b' ');
+  }
+  let decoded: Vec<u8> = percent_decode(&data).collect();
+  writer.write_all(&decoded)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Escape the HTML special characters `& < > " '`.
+pub fn process_html_escape(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+  let mut data = String::new();
+  reader.read_to_string(&mut data)?;
+  let mut escaped = String::with_capacity(data.len());
+  for c in data.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  writer.write_all(escaped.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Unescape numeric (`&#39;`, `&#x27;`) and common named HTML entities,
+/// unknown entities are left untouched.
+pub fn process_html_unescape(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+  let mut data = String::new();
+  reader.read_to_string(&mut data)?;
+  let mut unescaped = String::with_capacity(data.len());
+  let mut rest = data.as_str();
+  while let Some(start) = rest.find('&') {
+    unescaped.push_str(&rest[..start]);
+    rest = &rest[start..];
+    // entity names are short, don't scan the whole input for a `;`
+    let entity = rest
+      .char_indices()
+      .take(32)
+      .find(|(_, c)| *c == ';')
+      .and_then(|(end, _)| html_entity(&rest[1..end]).map(|c| (c, end)));
+    match entity {
+      Some((c, end)) => {
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        unescaped.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  unescaped.push_str(rest);
+  writer.write_all(unescaped.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Quoted-printable encode (RFC 2045). Line breaks in the input are kept as
+/// hard breaks, long lines get `=` soft breaks at 76 characters. One trailing
+/// line break is dropped rather than encoded as a final hard break.
+pub fn process_qp_encode(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  let data = trim_newline(&data);
+  let mut encoded = String::with_capacity(data.len() * 3 / 2);
+  for (i, line) in data.split(|b| *b == b'\n').enumerate() {
+    if i > 0 {
+      encoded.push('\n');
+    }
+    let (line, crlf) = match line.strip_suffix(b"\r") {
+      Some(line) => (line, true),
+      None => (line, false),
+    };
+    let mut column = 0;
+    for (j, &b) in line.iter().enumerate() {
+      let last = j + 1 == line.len();
+      let literal = matches!(b, b'!'..=b'<' | b'>'..=b'~') || (matches!(b, b' ' | b'\t') && !last);
+      let token = if literal {
+        (b as char).to_string()
+      } else {
+        format!("={:02X}", b)
+      };
+      // leave room for the `=` of a soft break, unless this ends the line
+      let limit = if last { QP_LINE_LEN } else { QP_LINE_LEN - 1 };
+      if column + token.len() > limit {
+        encoded.push_str("=\n");
+        column = 0;
+      }
+      encoded.push_str(&token);
+      column += token.len();
+    }
+    if crlf {
+      encoded.push('\r');
+    }
+  }
+  writer.write_all(encoded.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Quoted-printable decode, removing soft line breaks and trailing whitespace.
+/// Malformed `=` sequences are passed through as-is.
+pub fn process_qp_decode(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+  let mut data = Vec::new();
+  reader.read_to_end(&mut data)?;
+  let mut decoded = Vec::with_capacity(data.len());
+  let lines: Vec<&[u8]> = data.split(|b| *b == b'\n').collect();
+  for (i, line) in lines.iter().enumerate() {
+    let (line, crlf) = match line.strip_suffix(b"\r") {
+      Some(line) => (line, true),
+      None => (*line, false),
+    };
+    // trailing whitespace was added in transport, RFC 2045 section 6.7
+    let line = line.trim_ascii_end();
+    let (line, soft) = match line.strip_suffix(b"=") {
+      Some(line) => (line, true),
+      None => (line, false),
+    };
+    let mut j = 0;
+    while j < line.len() {
+      if line[j] == b'='
+        && let Some(hex) = line.get(j + 1..j + 3)
+        && let Ok(b) = u8::from_str_radix(std::str::from_utf8(hex).unwrap_or("-"), 16)
+      {
+        decoded.push(b);
+        j += 3;
+        continue;
+      }
+      decoded.push(line[j]);
+      j += 1;
+    }
+    if !soft && i + 1 < lines.len() {
+      decoded.extend_from_slice(if crlf { b"\r\n" } else { b"\n" });
+    }
+  }
+  writer.write_all(&decoded)?;
+  writer.flush()?;
+  Ok(())
+}
+
+fn html_entity(name: &str) -> Option<char> {
+  if let Some(num) = name.strip_prefix('#') {
+    let code = match num.strip_prefix(['x', 'X']) {
+      Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+      None => num.parse().ok()?,
+    };
+    return char::from_u32(code);
+  }
+  let c = match name {
+    "amp" => '&',
+    "lt" => '<',
+    "gt" => '>',
+    "quot" => '"',
+    "apos" => '\'',
+    "nbsp" => '\u{a0}',
+    "copy" => '©',
+    "reg" => '®',
+    "trade" => '™',
+    "hellip" => '…',
+    "mdash" => '—',
+    "ndash" => '–',
+    "lsquo" => '‘',
+    "rsquo" => '’',
+    "ldquo" => '“',
+    "rdquo" => '”',
+    "laquo" => '«',
+    "raquo" => '»',
+    "bull" => '•',
+    "middot" => '·',
+    "deg" => '°',
+    "plusmn" => '±',
+    "times" => '×',
+    "divide" => '÷',
+    "euro" => '€',
+    "pound" => '£',
+    "yen" => '¥',
+    "cent" => '¢',
+    "sect" => '§',
+    "para" => '¶',
+    _ => return None,
+  };
+  Some(c)
+}
+
+// drop one trailing `\n` or `\r\n`
+fn trim_newline(data: &[u8]) -> &[u8] {
+  match data.strip_suffix(b"\n") {
+    Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+    None => data,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Process = fn(&mut dyn Read, &mut dyn Write) -> Result<()>;
+
+  fn run(f: Process, input: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    f(&mut &input[..], &mut buf)?;
+    Ok(buf)
+  }
+
+  #[test]
+  fn test_url() -> Result<()> {
+    let url = "https://example.com/a b?q=ü&x=1#top";
+    let mut buf = Vec::new();
+    process_url_encode(&mut url.as_bytes(), &mut buf, false, false)?;
+    assert_eq!(
+      String::from_utf8(buf)?,
+      "https%3A%2F%2Fexample.com%2Fa%20b%3Fq%3D%C3%BC%26x%3D1%23top"
+    );
+    let mut buf = Vec::new();
+    process_url_encode(&mut url.as_bytes(), &mut buf, true, false)?;
+    assert_eq!(
+      String::from_utf8(buf)?,
+      "https://example.com/a%20b?q=%C3%BC&x=1#top"
+    );
+    let mut buf = Vec::new();
+    process_url_encode(&mut "a b&c".as_bytes(), &mut buf, false, true)?;
+    assert_eq!(buf, b"a+b%26c");
+    // the line break `echo` appends is not part of the value
+    for input in ["a b\n", "a b\r\n"] {
+      let mut buf = Vec::new();
+      process_url_encode(&mut input.as_bytes(), &mut buf, false, false)?;
+      assert_eq!(buf, b"a%20b");
+    }
+    let mut buf = Vec::new();
+    process_url_encode(&mut "a\n\n".as_bytes(), &mut buf, false, false)?;
+    assert_eq!(buf, b"a%0A");
+
+    let mut buf = Vec::new();
+    process_url_decode(&mut "q%3D%C3%BC+1".as_bytes(), &mut buf, false)?;
+    assert_eq!(String::from_utf8(buf)?, "q=ü+1");
+    let mut buf = Vec::new();
+    process_url_decode(&mut "q%3D%C3%BC+1".as_bytes(), &mut buf, true)?;
+    assert_eq!(String::from_utf8(buf)?, "q=ü 1");
+    Ok(())
+  }
+
+  #[test]
+  fn test_html() -> Result<()> {
+    let html = r#"<a href="x?a=1&b='2'">"#;
+    let escaped = run(process_html_escape, html.as_bytes())?;
+    assert_eq!(
+      String::from_utf8(escaped.clone())?,
+      "&lt;a href=&quot;x?a=1&amp;b=&#39;2&#39;&quot;&gt;"
+    );
+    assert_eq!(run(process_html_unescape, &escaped)?, html.as_bytes());
+    assert_eq!(
+      String::from_utf8(run(
+        process_html_unescape,
+        b"&copy; &#x1F600; &#8212; &bogus; AT&T &"
+      )?)?,
+      "© 😀 — &bogus; AT&T &"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_qp() -> Result<()> {
+    let text = "caf\u{e9} = 1 \nline two\t\r\nend";
+    let encoded = run(process_qp_encode, text.as_bytes())?;
+    assert_eq!(
+      String::from_utf8(encoded.clone())?,
+      "caf=C3=A9 =3D 1=20\nline two=09\r\nend"
+    );
+    assert_eq!(run(process_qp_decode, &encoded)?, text.as_bytes());
+
+    let long = "x".repeat(200);
+    let encoded = run(process_qp_encode, long.as_bytes())?;
+    assert!(
+      String::from_utf8(encoded.clone())?
+        .lines()
+        .all(|l| l.len() <= 76)
+    );
+    assert_eq!(run(process_qp_decode, &encoded)?, long.as_bytes());
+
+    assert_eq!(run(process_qp_decode, b"a=\r\nb =ZZ =4")?, b"ab =ZZ =4");
+    assert_eq!(run(process_qp_encode, b"a b\n")?, b"a b");
+    assert_eq!(run(process_qp_encode, b"a \r\n")?, b"a=20");
+    assert_eq!(run(process_qp_encode, b"a\r")?, b"a\r");
+    Ok(())
+  }
+}
@@ -1,45 +1,564 @@
+use anyhow::bail;
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-extern crate zxcvbn;
-
-const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
-const NUMBER: &[u8] = b"123456789";
-const SYMBOL: &[u8] = b"!@#$%&^*_";
-
-pub fn process_genpass(
-  length: u8,
-  upper: bool,
-  lower: bool,
-  number: bool,
-  symbol: bool,
+use rand::{Rng, RngCore, SeedableRng, thread_rng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use zxcvbn::zxcvbn;
+
+const UPPER: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijkmnpqrstuvwxyz";
+const NUMBER: &str = "23456789";
+const SYMBOL: &str = "!@#$%&^*_";
+// the classes above leave out look-alikes (`I`/`l`/`1`, `O`/`o`/`0`),
+// these are used with `allow_ambiguous`
+const UPPER_ALL: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER_ALL: &str = "abcdefghijklmnopqrstuvwxyz";
+const NUMBER_ALL: &str = "0123456789";
+const EFF_WORDLIST: &str = include_str!("../../assets/eff_large_wordlist.txt");
+
+#[derive(Debug, Clone)]
+pub struct GenPassOptions {
+  pub length: u8,
+  pub upper: bool,
+  pub lower: bool,
+  pub number: bool,
+  pub symbol: bool,
+  /// Replaces the default symbol set
+  pub symbols: Option<String>,
+  /// Replaces all character classes with a single custom alphabet
+  pub alphabet: Option<String>,
+  /// Characters never used in the password
+  pub exclude: String,
+  /// Keep look-alike characters such as `0`/`O` and `1`/`l` in the built-in
+  /// classes, a custom `symbols` or `alphabet` is always used as given
+  pub allow_ambiguous: bool,
+}
+
+// a character class and how many of its characters the password must contain
+struct CharClass {
+  name: &'static str,
+  chars: Vec<char>,
+  min: usize,
+}
+
+pub struct PassphraseOptions<'a> {
+  pub words: usize,
+  pub separator: &'a str,
+  pub capitalize: bool,
+  /// Append a random digit to one randomly chosen word
+  pub digit: bool,
+}
+
+pub struct Passphrase {
+  pub passphrase: String,
+  pub entropy: f64,
+}
+
+/// A generated secret with its strength estimates, one row of batch output.
+#[derive(Debug, Serialize)]
+pub struct PasswordReport {
+  pub password: String,
+  pub score: u8,
+  pub entropy: f64,
+  /// zxcvbn estimate for an offline attack on a slow hash (1e4 guesses/s)
+  pub crack_time: String,
+}
+
+/// The RNG behind every generator: the OS seeded CSPRNG, or a ChaCha20 stream
+/// derived from `seed` for reproducible, and therefore insecure, test fixtures.
+pub fn genpass_rng(seed: Option<u64>) -> Box<dyn RngCore> {
+  match seed {
+    Some(seed) => Box::new(ChaCha20Rng::seed_from_u64(seed)),
+    None => Box::new(thread_rng()),
+  }
+}
+
+/// Generate a password of `length` characters from the enabled classes,
+/// with at least one character of every enabled class.
+pub fn process_genpass(options: &GenPassOptions) -> anyhow::Result<String> {
+  process_genpass_with_rng(options, &mut thread_rng())
+}
+
+/// Like [`process_genpass`], drawing from `rng`.
+///
+/// The password is uniform over all strings that contain every enabled
+/// class, see [`process_genpass_with_minimums`].
+pub fn process_genpass_with_rng(
+  options: &GenPassOptions,
+  rng: &mut dyn RngCore,
 ) -> anyhow::Result<String> {
-  let mut password = Vec::new();
-  let mut rng = thread_rng();
-  let mut chars = Vec::new();
+  process_genpass_with_minimums(options, &[], rng)
+}
 
-  if upper {
-    chars.extend_from_slice(UPPER);
-    password.push(*UPPER.choose(&mut rng).expect(""));
+/// Like [`process_genpass_with_rng`], with at least `min` characters of each
+/// named class (`uppercase`, `lowercase`, `number`, `symbol`) in `minimums`.
+///
+/// How many characters each class gets is drawn in proportion to the number
+/// of passwords with that split, then the characters are drawn from their
+/// class and shuffled, so every password that meets the minimums is equally
+/// likely.
+pub(crate) fn process_genpass_with_minimums(
+  options: &GenPassOptions,
+  minimums: &[(&str, usize)],
+  rng: &mut dyn RngCore,
+) -> anyhow::Result<String> {
+  let classes = required_classes(options, minimums)?;
+  let counts = class_counts(&classes, options.length as usize);
+
+  let mut password = Vec::with_capacity(options.length as usize);
+  let mut remaining = options.length as usize;
+  for (i, class) in classes.iter().enumerate().rev() {
+    let size = (class.chars.len() as f64).log2();
+    let splits = class.min..=remaining;
+    let weights: Vec<f64> = splits
+      .clone()
+      .map(|k| counts.table[i][remaining - k] + k as f64 * size - counts.log2_fact[k])
+      .collect();
+    let max = weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let split = WeightedIndex::new(weights.iter().map(|w| (w - max).exp2()))?;
+    let k = class.min + split.sample(rng);
+    for _ in 0..k {
+      password.push(*class.chars.choose(rng).expect("classes won't be empty"));
+    }
+    remaining -= k;
   }
-  if lower {
-    chars.extend_from_slice(LOWER);
-    password.push(*LOWER.choose(&mut rng).expect(""));
+  password.shuffle(rng);
+
+  Ok(password.into_iter().collect())
+}
+
+/// Entropy in bits of a password from [`process_genpass`].
+pub fn genpass_entropy(options: &GenPassOptions) -> anyhow::Result<f64> {
+  genpass_entropy_with_minimums(options, &[])
+}
+
+/// Entropy in bits of a password from [`process_genpass_with_minimums`],
+/// log2 of the number of passwords that meet the class minimums.
+pub(crate) fn genpass_entropy_with_minimums(
+  options: &GenPassOptions,
+  minimums: &[(&str, usize)],
+) -> anyhow::Result<f64> {
+  let classes = required_classes(options, minimums)?;
+  let length = options.length as usize;
+  let counts = class_counts(&classes, length);
+  Ok(counts.table[classes.len()][length] + counts.log2_fact[length])
+}
+
+struct ClassCounts {
+  log2_fact: Vec<f64>,
+  /// log2 of the exponential generating function coefficients:
+  /// `table[i][n]` is the number of n character strings over the first `i`
+  /// classes meeting their minimums, over n!
+  table: Vec<Vec<f64>>,
+}
+
+fn class_counts(classes: &[CharClass], length: usize) -> ClassCounts {
+  let log2_fact: Vec<f64> = (0..=length)
+    .scan(0.0, |acc, k| {
+      *acc += (k.max(1) as f64).log2();
+      Some(*acc)
+    })
+    .collect();
+  let mut counts = vec![f64::NEG_INFINITY; length + 1];
+  counts[0] = 0.0;
+  let mut table = vec![counts.clone()];
+  for class in classes {
+    let size = (class.chars.len() as f64).log2();
+    counts = (0..=length)
+      .map(|n| log2_sum((class.min..=n).map(|k| counts[n - k] + k as f64 * size - log2_fact[k])))
+      .collect();
+    table.push(counts.clone());
+  }
+  ClassCounts { log2_fact, table }
+}
+
+/// Score `password` with zxcvbn and bundle it with its entropy estimate.
+pub fn password_report(password: String, entropy: f64) -> anyhow::Result<PasswordReport> {
+  let estimate = zxcvbn(&password, &[])?;
+  let crack_time = estimate
+    .crack_times()
+    .offline_slow_hashing_1e4_per_second()
+    .to_string();
+  Ok(PasswordReport {
+    score: estimate.score(),
+    password,
+    entropy: (entropy * 10.0).round() / 10.0,
+    crack_time,
+  })
+}
+
+// The enabled classes with their required counts, at least one of each, and
+// checked against the password length.
+fn required_classes(
+  options: &GenPassOptions,
+  minimums: &[(&str, usize)],
+) -> anyhow::Result<Vec<CharClass>> {
+  let mut classes = char_classes(options)?;
+  for class in &mut classes {
+    class.min = minimums
+      .iter()
+      .find(|(name, _)| *name == class.name)
+      .map_or(1, |(_, min)| (*min).max(1));
   }
-  if number {
-    chars.extend_from_slice(NUMBER);
-    password.push(*NUMBER.choose(&mut rng).expect(""));
+  let required: usize = classes.iter().map(|c| c.min).sum();
+  if (options.length as usize) < required {
+    bail!(
+      "Password length {} is shorter than the {} required characters",
+      options.length,
+      required
+    );
   }
-  if symbol {
-    chars.extend_from_slice(SYMBOL);
-    password.push(*SYMBOL.choose(&mut rng).expect(""));
+  Ok(classes)
+}
+
+// Build the character classes a password is drawn from, after applying the
+// custom alphabet/symbols and the exclude list. Look-alikes are only dropped
+// from the built-in classes, never from sets the user spelled out.
+fn char_classes(options: &GenPassOptions) -> anyhow::Result<Vec<CharClass>> {
+  let classes: Vec<(&'static str, &str)> = match &options.alphabet {
+    Some(alphabet) => vec![("alphabet", alphabet.as_str())],
+    None => {
+      let (upper, lower, number) = if options.allow_ambiguous {
+        (UPPER_ALL, LOWER_ALL, NUMBER_ALL)
+      } else {
+        (UPPER, LOWER, NUMBER)
+      };
+      let symbol = options.symbols.as_deref().unwrap_or(SYMBOL);
+      [
+        (options.upper, "uppercase", upper),
+        (options.lower, "lowercase", lower),
+        (options.number, "number", number),
+        (options.symbol, "symbol", symbol),
+      ]
+      .into_iter()
+      .filter(|(enabled, _, _)| *enabled)
+      .map(|(_, name, chars)| (name, chars))
+      .collect()
+    }
+  };
+  if classes.is_empty() {
+    bail!("At least one character class must be enabled");
   }
 
-  for _ in 0..(length - password.len() as u8) {
-    let c = chars.choose(&mut rng).expect("chars won't be empty");
-    password.push(*c);
+  let mut ret = Vec::with_capacity(classes.len());
+  for (name, chars) in classes {
+    let mut chars: Vec<char> = chars
+      .chars()
+      .filter(|c| !options.exclude.contains(*c))
+      .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    if chars.is_empty() {
+      bail!("No characters left in the {} class", name);
+    }
+    ret.push(CharClass {
+      name,
+      chars,
+      min: 1,
+    });
   }
+  Ok(ret)
+}
+
+/// Generate a diceware passphrase from `wordlist`, or from the embedded EFF
+/// large wordlist when none is given.
+///
+/// Wordlist lines may be plain words or EFF style `<dice>\t<word>`; the last
+/// field of each line is used.
+pub fn process_passphrase(
+  wordlist: Option<&str>,
+  options: &PassphraseOptions,
+) -> anyhow::Result<Passphrase> {
+  process_passphrase_with_rng(wordlist, options, &mut thread_rng())
+}
 
-  let password = String::from_utf8(password)?;
-  Ok(password)
+/// Like [`process_passphrase`], drawing from `rng`.
+pub fn process_passphrase_with_rng(
+  wordlist: Option<&str>,
+  options: &PassphraseOptions,
+  rng: &mut dyn RngCore,
+) -> anyhow::Result<Passphrase> {
+  let mut list = parse_wordlist(wordlist.unwrap_or(EFF_WORDLIST));
+  list.sort_unstable();
+  list.dedup();
+  if list.len() < 2 {
+    bail!("Wordlist must contain at least 2 distinct words");
+  }
+  if options.words == 0 {
+    bail!("Passphrase must contain at least 1 word");
+  }
+
+  let mut words = Vec::with_capacity(options.words);
+  for _ in 0..options.words {
+    let word = *list.choose(rng).expect("wordlist won't be empty");
+    words.push(if options.capitalize {
+      capitalize(word)
+    } else {
+      word.to_string()
+    });
+  }
+  let mut entropy = options.words as f64 * (list.len() as f64).log2();
+  if options.digit {
+    let index = rng.gen_range(0..words.len());
+    words[index].push(rng.gen_range('0'..='9'));
+    entropy += (10.0 * options.words as f64).log2();
+  }
+
+  Ok(Passphrase {
+    passphrase: words.join(options.separator),
+    entropy,
+  })
+}
+
+impl Default for GenPassOptions {
+  fn default() -> Self {
+    Self {
+      length: 16,
+      upper: true,
+      lower: true,
+      number: true,
+      symbol: true,
+      symbols: None,
+      alphabet: None,
+      exclude: String::new(),
+      allow_ambiguous: false,
+    }
+  }
+}
+
+// log2(2^a + 2^b + ...) without leaving log space
+fn log2_sum(terms: impl Iterator<Item = f64>) -> f64 {
+  let terms: Vec<f64> = terms.collect();
+  let max = terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+  if max == f64::NEG_INFINITY {
+    return max;
+  }
+  max + terms.iter().map(|t| (t - max).exp2()).sum::<f64>().log2()
+}
+
+fn parse_wordlist(content: &str) -> Vec<&str> {
+  content
+    .lines()
+    .filter_map(|line| line.split_whitespace().last())
+    .collect()
+}
+
+fn capitalize(word: &str) -> String {
+  let mut chars = word.chars();
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => String::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_genpass_classes() -> anyhow::Result<()> {
+    let options = GenPassOptions {
+      length: 32,
+      upper: false,
+      symbol: false,
+      ..Default::default()
+    };
+    let ret = process_genpass(&options)?;
+    assert_eq!(ret.len(), 32);
+    assert!(ret.chars().all(|c| LOWER.contains(c) || NUMBER.contains(c)));
+    assert!(ret.chars().any(|c| NUMBER.contains(c)));
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_validation() {
+    let none = GenPassOptions {
+      upper: false,
+      lower: false,
+      number: false,
+      symbol: false,
+      ..Default::default()
+    };
+    assert!(process_genpass(&none).is_err());
+    let short = GenPassOptions {
+      length: 3,
+      ..Default::default()
+    };
+    assert!(process_genpass(&short).is_err());
+    let exact = GenPassOptions {
+      length: 4,
+      ..Default::default()
+    };
+    assert!(process_genpass(&exact).is_ok());
+  }
+
+  #[test]
+  fn test_genpass_custom_charsets() -> anyhow::Result<()> {
+    let options = GenPassOptions {
+      length: 64,
+      symbols: Some("-+".into()),
+      exclude: "ABC".into(),
+      ..Default::default()
+    };
+    let ret = process_genpass(&options)?;
+    assert!(ret.contains(['-', '+']));
+    assert!(!ret.contains(['A', 'B', 'C', '!', '0', 'O']));
+
+    let options = GenPassOptions {
+      length: 64,
+      alphabet: Some("xy0".into()),
+      ..Default::default()
+    };
+    assert!(
+      process_genpass(&options)?
+        .chars()
+        .all(|c| "xy0".contains(c))
+    );
+
+    // the built-in classes drop them unless asked
+    let ret = process_genpass(&GenPassOptions {
+      length: 255,
+      ..Default::default()
+    })?;
+    assert!(!ret.contains(['I', 'O', 'l', 'o', '0', '1']));
+
+    // an explicit alphabet or symbol set keeps its look-alikes
+    let hex = GenPassOptions {
+      length: 64,
+      alphabet: Some("0123456789abcdef".into()),
+      ..Default::default()
+    };
+    assert_eq!(genpass_entropy(&hex)?, 256.0);
+    let ret = (0..20)
+      .map(|_| process_genpass(&hex))
+      .collect::<anyhow::Result<String>>()?;
+    assert!(ret.contains('0') && ret.contains('1'));
+    let options = GenPassOptions {
+      length: 64,
+      symbols: Some("|`".into()),
+      ..Default::default()
+    };
+    assert!(process_genpass(&options)?.contains(['|', '`']));
+
+    let options = GenPassOptions {
+      symbols: Some("!".into()),
+      exclude: "!".into(),
+      ..Default::default()
+    };
+    assert!(process_genpass(&options).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_shuffles_required_classes() -> anyhow::Result<()> {
+    let options = GenPassOptions {
+      length: 4,
+      ..Default::default()
+    };
+    let mut rng = genpass_rng(Some(1));
+    let first_is_upper = (0..200)
+      .map(|_| process_genpass_with_rng(&options, &mut rng))
+      .collect::<anyhow::Result<Vec<_>>>()?
+      .iter()
+      .filter(|p| p.starts_with(|c: char| c.is_ascii_uppercase()))
+      .count();
+    assert!(first_is_upper < 150, "prefix is predictable");
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_is_uniform_over_minimums() -> anyhow::Result<()> {
+    // "2" plus one of "!?": of the 64 valid length 4 strings, 32 have a
+    // single "2", drawing the fill from all classes would give 4/9 of them
+    let options = GenPassOptions {
+      length: 4,
+      upper: false,
+      lower: false,
+      symbols: Some("!?".into()),
+      exclude: "3456789".into(),
+      ..Default::default()
+    };
+    assert_eq!(genpass_entropy(&options)?, 6.0);
+    let mut rng = genpass_rng(Some(1));
+    let single = (0..20000)
+      .map(|_| process_genpass_with_rng(&options, &mut rng))
+      .collect::<anyhow::Result<Vec<_>>>()?
+      .iter()
+      .filter(|p| p.matches('2').count() == 1)
+      .count();
+    assert!((9600..10400).contains(&single), "{single} of 20000");
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_seed_is_reproducible() -> anyhow::Result<()> {
+    let options = GenPassOptions::default();
+    let first = process_genpass_with_rng(&options, &mut genpass_rng(Some(42)))?;
+    let second = process_genpass_with_rng(&options, &mut genpass_rng(Some(42)))?;
+    let other = process_genpass_with_rng(&options, &mut genpass_rng(Some(43)))?;
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+
+    let passphrase = PassphraseOptions {
+      words: 4,
+      separator: "-",
+      capitalize: false,
+      digit: true,
+    };
+    let first = process_passphrase_with_rng(None, &passphrase, &mut genpass_rng(Some(42)))?;
+    let second = process_passphrase_with_rng(None, &passphrase, &mut genpass_rng(Some(42)))?;
+    assert_eq!(first.passphrase, second.passphrase);
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_entropy() -> anyhow::Result<()> {
+    let options = GenPassOptions {
+      length: 10,
+      alphabet: Some("ab".into()),
+      ..Default::default()
+    };
+    assert_eq!(genpass_entropy(&options)?, 10.0);
+    let report = password_report(process_genpass(&options)?, 10.0)?;
+    assert!(report.score <= 4);
+    assert!(!report.crack_time.is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn test_passphrase() -> anyhow::Result<()> {
+    let options = PassphraseOptions {
+      words: 6,
+      separator: "-",
+      capitalize: true,
+      digit: true,
+    };
+    let ret = process_passphrase(None, &options)?;
+    let words: Vec<_> = ret.passphrase.split('-').collect();
+    assert_eq!(words.len(), 6);
+    assert!(words.iter().all(|w| w.starts_with(char::is_uppercase)));
+    assert_eq!(
+      ret.passphrase.chars().filter(char::is_ascii_digit).count(),
+      1
+    );
+    // 6 words from 7776 is the classic ~77.5 bits, plus the digit
+    assert!(ret.entropy > 77.5);
+    Ok(())
+  }
+
+  #[test]
+  fn test_passphrase_custom_wordlist() -> anyhow::Result<()> {
+    let options = PassphraseOptions {
+      words: 4,
+      separator: " ",
+      capitalize: false,
+      digit: false,
+    };
+    let ret = process_passphrase(Some("11111\tred\nblue\n"), &options)?;
+    assert!(ret.passphrase.split(' ').all(|w| w == "red" || w == "blue"));
+    assert_eq!(ret.entropy, 4.0);
+    assert!(process_passphrase(Some("red\nred\n"), &options).is_err());
+    Ok(())
+  }
 }
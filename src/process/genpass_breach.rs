@@ -0,0 +1,131 @@
+use anyhow::Result;
+use md4::Md4;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::cli::BreachHashFormat;
+
+/// A local copy of a Have I Been Pwned style password hash list.
+///
+/// The file holds one `HASH:COUNT` line per breached password (the count is
+/// optional), sorted by hash, which is how the HIBP downloader writes it.
+/// Lookups binary search the file on disk, so multi-GB lists are never loaded
+/// into memory and no request ever leaves the machine.
+pub struct BreachList {
+  reader: BufReader<File>,
+  len: u64,
+  format: BreachHashFormat,
+}
+
+impl BreachList {
+  pub fn open(path: impl AsRef<Path>, format: BreachHashFormat) -> Result<Self> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    Ok(Self {
+      reader: BufReader::new(file),
+      len,
+      format,
+    })
+  }
+
+  /// Return how often `password` was seen in breaches, or `None` if it is not listed.
+  pub fn lookup(&mut self, password: &str) -> Result<Option<u64>> {
+    let target = self.hash(password);
+    self.lookup_hash(&target)
+  }
+
+  /// Look up an uppercase hex hash directly.
+  pub fn lookup_hash(&mut self, target: &str) -> Result<Option<u64>> {
+    let (mut lo, mut hi) = (0, self.len);
+    // invariant: a line matching `target` starts within lo..hi
+    while lo < hi {
+      let mid = lo + (hi - lo) / 2;
+      let Some((start, line)) = self.line_at(mid)? else {
+        hi = mid;
+        continue;
+      };
+      if start >= hi {
+        hi = mid;
+        continue;
+      }
+      let (hash, count) = line
+        .trim_end()
+        .split_once(':')
+        .unwrap_or((line.trim_end(), ""));
+      match hash.to_ascii_uppercase().as_str().cmp(target) {
+        Ordering::Equal => return Ok(Some(count.parse().unwrap_or(1))),
+        Ordering::Less => lo = start + line.len() as u64,
+        Ordering::Greater => hi = mid,
+      }
+    }
+    Ok(None)
+  }
+
+  fn hash(&self, password: &str) -> String {
+    let digest = match self.format {
+      BreachHashFormat::Sha1 => Sha1::digest(password.as_bytes()).to_vec(),
+      BreachHashFormat::Ntlm => {
+        let utf16: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        Md4::digest(utf16).to_vec()
+      }
+    };
+    digest.iter().map(|b| format!("{:02X}", b)).collect()
+  }
+
+  // The first complete line starting at or after `pos`, with its offset.
+  fn line_at(&mut self, pos: u64) -> Result<Option<(u64, String)>> {
+    let mut start = pos;
+    if pos > 0 {
+      self.reader.seek(SeekFrom::Start(pos - 1))?;
+      let mut skipped = Vec::new();
+      start = pos - 1 + self.reader.read_until(b'\n', &mut skipped)? as u64;
+    } else {
+      self.reader.seek(SeekFrom::Start(0))?;
+    }
+    let mut line = String::new();
+    if self.reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    Ok(Some((start, line)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_breach_list_sha1() -> Result<()> {
+    let mut list = BreachList::open("fuxtures/hibp_sha1.txt", BreachHashFormat::Sha1)?;
+    for password in ["password", "123456", "sunshine", "trustno1", "hunter2"] {
+      assert!(list.lookup(password)?.is_some(), "{} not found", password);
+    }
+    assert_eq!(list.lookup("password")?, Some(2716507));
+    assert_eq!(list.lookup("Wy8_W5qd5d!@3#eV")?, None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_breach_list_finds_every_line() -> Result<()> {
+    let content = std::fs::read_to_string("fuxtures/hibp_sha1.txt")?;
+    let mut list = BreachList::open("fuxtures/hibp_sha1.txt", BreachHashFormat::Sha1)?;
+    for line in content.lines() {
+      let (hash, count) = line.split_once(':').unwrap();
+      assert_eq!(list.lookup_hash(hash)?, Some(count.parse()?));
+    }
+    assert_eq!(list.lookup_hash(&"0".repeat(40))?, None);
+    assert_eq!(list.lookup_hash(&"F".repeat(40))?, None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_breach_list_ntlm() -> Result<()> {
+    let mut list = BreachList::open("fuxtures/hibp_ntlm.txt", BreachHashFormat::Ntlm)?;
+    assert_eq!(list.lookup("password")?, Some(3861493));
+    assert_eq!(list.lookup("Password")?, None);
+    Ok(())
+  }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use zxcvbn::zxcvbn;
+
+use super::genpass_breach::BreachList;
+
+/// zxcvbn assessment of a single candidate password.
+///
+/// The password itself is deliberately left out so reports can be logged.
+#[derive(Debug, Serialize)]
+pub struct StrengthReport {
+  pub line: usize,
+  pub score: u8,
+  pub warning: Option<String>,
+  pub suggestions: Vec<String>,
+  pub crack_times: CrackTimes,
+  /// Times the password was seen in the breach list, `None` when no list was given
+  pub breached: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrackTimes {
+  pub online_throttled: String,
+  pub online_unthrottled: String,
+  pub offline_slow_hash: String,
+  pub offline_fast_hash: String,
+}
+
+/// Score every non-empty line of `reader` as a candidate password.
+///
+/// `user_inputs` are context words (user names, company names...) that zxcvbn
+/// penalizes when they show up in a password.
+pub fn process_check_passwords(
+  reader: &mut dyn Read,
+  user_inputs: &[&str],
+  mut breach_list: Option<&mut BreachList>,
+) -> Result<Vec<StrengthReport>> {
+  let mut ret = Vec::new();
+  for (index, line) in BufReader::new(reader).lines().enumerate() {
+    let line = line?;
+    let password = line.trim_end_matches('\r');
+    if password.is_empty() {
+      continue;
+    }
+    let mut report = check_password(index + 1, password, user_inputs)?;
+    if let Some(list) = breach_list.as_deref_mut() {
+      report.breached = Some(list.lookup(password)?.unwrap_or(0));
+    }
+    ret.push(report);
+  }
+  Ok(ret)
+}
+
+fn check_password(line: usize, password: &str, user_inputs: &[&str]) -> Result<StrengthReport> {
+  let estimate = zxcvbn(password, user_inputs)?;
+  let (warning, suggestions) = match estimate.feedback() {
+    Some(feedback) => (
+      feedback.warning().map(|w| w.to_string()),
+      feedback
+        .suggestions()
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+    ),
+    None => (None, Vec::new()),
+  };
+  let times = estimate.crack_times();
+  Ok(StrengthReport {
+    line,
+    score: estimate.score(),
+    warning,
+    suggestions,
+    crack_times: CrackTimes {
+      online_throttled: times.online_throttling_100_per_hour().to_string(),
+      online_unthrottled: times.online_no_throttling_10_per_second().to_string(),
+      offline_slow_hash: times.offline_slow_hashing_1e4_per_second().to_string(),
+      offline_fast_hash: times.offline_fast_hashing_1e10_per_second().to_string(),
+    },
+    breached: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_passwords() -> Result<()> {
+    let mut input = "password\n\nCorrect-Horse-Battery-Staple-42\n".as_bytes();
+    let ret = process_check_passwords(&mut input, &[], None)?;
+    assert_eq!(ret.len(), 2);
+    assert_eq!(ret[0].line, 1);
+    assert_eq!(ret[0].score, 0);
+    assert!(ret[0].warning.is_some());
+    assert_eq!(ret[1].line, 3);
+    assert_eq!(ret[1].score, 4);
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_passwords_user_inputs() -> Result<()> {
+    // made-up words zxcvbn has no dictionary entry for
+    let password = "Zorblaxquintaro7".as_bytes();
+    let without = process_check_passwords(&mut &password[..], &[], None)?;
+    let with = process_check_passwords(&mut &password[..], &["zorblax", "quintaro"], None)?;
+    assert_eq!(without[0].score, 4);
+    assert!(with[0].score < without[0].score);
+    assert_ne!(
+      with[0].crack_times.offline_slow_hash,
+      without[0].crack_times.offline_slow_hash
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_passwords_breached() -> Result<()> {
+    let mut list = BreachList::open("fuxtures/hibp_sha1.txt", crate::cli::BreachHashFormat::Sha1)?;
+    let mut input = "password\nWy8_W5qd5d!@3#eV\n".as_bytes();
+    let ret = process_check_passwords(&mut input, &[], Some(&mut list))?;
+    assert_eq!(ret[0].breached, Some(2716507));
+    assert_eq!(ret[1].breached, Some(0));
+    Ok(())
+  }
+}
@@ -0,0 +1,115 @@
+use anyhow::{Result, bail};
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+const CONSONANTS: &str = "bcdfghjkmnprstvwxz";
+const VOWELS: &str = "aeiu";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%&^*_";
+
+/// Generate a pronounceable password of alternating consonants and vowels,
+/// e.g. `bamiketu`, returning it with its entropy in bits.
+///
+/// Look-alike letters (`l`, `o`) are left out, as in the default genpass classes.
+pub fn process_pronounceable(length: u8, rng: &mut dyn RngCore) -> Result<(String, f64)> {
+  if length == 0 {
+    bail!("Password length must be at least 1");
+  }
+  let template: String = (0..length)
+    .map(|i| if i % 2 == 0 { 'c' } else { 'v' })
+    .collect();
+  process_pattern(&template, rng)
+}
+
+/// Generate a password from a template, returning it with its entropy in bits.
+///
+/// | placeholder | characters            |
+/// |-------------|-----------------------|
+/// | `c` / `C`   | lower/upper consonant |
+/// | `v` / `V`   | lower/upper vowel     |
+/// | `a` / `A`   | lower/upper letter    |
+/// | `9`         | digit                 |
+/// | `!`         | symbol                |
+/// | `\x`        | literal `x`           |
+///
+/// Any other character is copied as is, so `Cvccvc-9999-!` gives e.g. `Tavrek-4821-%`.
+pub fn process_pattern(template: &str, rng: &mut dyn RngCore) -> Result<(String, f64)> {
+  let mut password = String::with_capacity(template.len());
+  let mut entropy = 0.0;
+  let mut chars = template.chars();
+  while let Some(c) = chars.next() {
+    let set = match c {
+      'c' => CONSONANTS.to_string(),
+      'C' => CONSONANTS.to_uppercase(),
+      'v' => VOWELS.to_string(),
+      'V' => VOWELS.to_uppercase(),
+      'a' => format!("{}{}", CONSONANTS, VOWELS),
+      'A' => format!("{}{}", CONSONANTS, VOWELS).to_uppercase(),
+      '9' => DIGITS.to_string(),
+      '!' => SYMBOLS.to_string(),
+      '\\' => match chars.next() {
+        Some(literal) => {
+          password.push(literal);
+          continue;
+        }
+        None => bail!("Pattern ends with a dangling escape"),
+      },
+      literal => {
+        password.push(literal);
+        continue;
+      }
+    };
+    let set: Vec<char> = set.chars().collect();
+    password.push(*set.choose(rng).expect("placeholder sets won't be empty"));
+    entropy += (set.len() as f64).log2();
+  }
+  if entropy == 0.0 {
+    bail!(
+      "Pattern {:?} has no placeholders, the password would be constant",
+      template
+    );
+  }
+  Ok((password, entropy))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::thread_rng;
+
+  #[test]
+  fn test_pattern() -> Result<()> {
+    let (password, entropy) = process_pattern("Cvccvc-9999-!", &mut thread_rng())?;
+    let chars: Vec<char> = password.chars().collect();
+    assert_eq!(chars.len(), 13);
+    assert!(chars[0].is_ascii_uppercase());
+    assert!(VOWELS.contains(chars[1]));
+    assert!(chars[7..11].iter().all(char::is_ascii_digit));
+    assert_eq!(chars[6], '-');
+    assert!(SYMBOLS.contains(chars[12]));
+    let expected = 4.0 * 18f64.log2() + 2.0 * 4f64.log2() + 4.0 * 10f64.log2() + 9f64.log2();
+    assert!((entropy - expected).abs() < 1e-9);
+    Ok(())
+  }
+
+  #[test]
+  fn test_pattern_escape() -> Result<()> {
+    let (password, _) = process_pattern("\\c\\9-99", &mut thread_rng())?;
+    assert!(password.starts_with("c9-"));
+    assert!(process_pattern("abc\\", &mut thread_rng()).is_err());
+    assert!(process_pattern("---", &mut thread_rng()).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_pronounceable() -> Result<()> {
+    let (password, entropy) = process_pronounceable(8, &mut thread_rng())?;
+    assert_eq!(password.len(), 8);
+    for (i, c) in password.chars().enumerate() {
+      let set = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+      assert!(set.contains(c));
+    }
+    assert!((entropy - 4.0 * (18f64.log2() + 2.0)).abs() < 1e-9);
+    Ok(())
+  }
+}
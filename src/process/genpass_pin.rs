@@ -0,0 +1,171 @@
+use anyhow::{Result, bail};
+use rand::{Rng, RngCore};
+
+const MIN_PIN_LENGTH: u8 = 4;
+const MAX_PIN_LENGTH: u8 = 12;
+// rejection sampling budget, weak PINs are a small fraction of the space
+const MAX_ATTEMPTS: usize = 10_000;
+
+// most frequently chosen PINs in leaked data sets
+const COMMON_PINS: &[&str] = &[
+  "1004",
+  "1010",
+  "1122",
+  "1212",
+  "1313",
+  "1342",
+  "2001",
+  "2580",
+  "4321",
+  "5683",
+  "6969",
+  "7777",
+  "0852",
+  "112233",
+  "121212",
+  "123123",
+  "131313",
+  "159753",
+  "654321",
+  "696969",
+  "789456",
+  "102030",
+  "147258",
+  "159357",
+  "258456",
+  "753951",
+  "987654",
+  "1234567",
+  "7654321",
+  "12344321",
+  "11223344",
+  "12121212",
+  "88888888",
+  "147258369",
+  "987654321",
+];
+
+/// Generate a numeric PIN of `length` digits that is not a sequence, a run of
+/// repeats, a repeated block, a plausible date or a commonly chosen PIN.
+pub fn process_pin(length: u8, rng: &mut dyn RngCore) -> Result<String> {
+  if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+    bail!(
+      "PIN length must be between {} and {}",
+      MIN_PIN_LENGTH,
+      MAX_PIN_LENGTH
+    );
+  }
+  for _ in 0..MAX_ATTEMPTS {
+    let pin: String = (0..length).map(|_| rng.gen_range('0'..='9')).collect();
+    if pin_weakness(&pin)?.is_none() {
+      return Ok(pin);
+    }
+  }
+  bail!(
+    "Could not generate a strong PIN after {} attempts",
+    MAX_ATTEMPTS
+  )
+}
+
+/// Explain why `pin` is easy to guess, or `None` if no weak pattern matches.
+///
+/// Fails unless `pin` is made of at least 2 ASCII digits.
+pub fn pin_weakness(pin: &str) -> Result<Option<&'static str>> {
+  if pin.len() < 2 || !pin.bytes().all(|b| b.is_ascii_digit()) {
+    bail!("PIN must be at least 2 digits 0-9");
+  }
+  Ok(weakness(pin))
+}
+
+// `pin` is ASCII digits only, so byte indexing is safe
+fn weakness(pin: &str) -> Option<&'static str> {
+  let digits: Vec<i8> = pin.bytes().map(|b| (b - b'0') as i8).collect();
+  if COMMON_PINS.contains(&pin) {
+    return Some("common PIN");
+  }
+  let step = digits[1] - digits[0];
+  if digits.windows(2).all(|w| w[1] - w[0] == step) {
+    return Some("arithmetic sequence");
+  }
+  let runs = digits.windows(3).any(|w| {
+    let (a, b) = (w[1] - w[0], w[2] - w[1]);
+    a == b && a.abs() <= 1
+  });
+  if runs {
+    return Some("three repeated or consecutive digits");
+  }
+  let len = digits.len();
+  if (1..len).any(|period| len.is_multiple_of(period) && pin[..len - period] == pin[period..]) {
+    return Some("repeated block");
+  }
+  if is_date(pin) {
+    return Some("looks like a date");
+  }
+  None
+}
+
+fn is_date(pin: &str) -> bool {
+  let num = |s: &str| s.parse::<u32>().unwrap_or(0);
+  let day_month = |d: &str, m: &str| (1..=31).contains(&num(d)) && (1..=12).contains(&num(m));
+  let year = |y: &str| (1900..=2099).contains(&num(y));
+  match pin.len() {
+    4 => day_month(&pin[..2], &pin[2..]) || day_month(&pin[2..], &pin[..2]) || year(pin),
+    6 => {
+      day_month(&pin[..2], &pin[2..4])
+        || day_month(&pin[2..4], &pin[..2])
+        || day_month(&pin[4..], &pin[2..4])
+    }
+    8 => {
+      (year(&pin[4..]) && (day_month(&pin[..2], &pin[2..4]) || day_month(&pin[2..4], &pin[..2])))
+        || (year(&pin[..4]) && day_month(&pin[6..], &pin[4..6]))
+    }
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::thread_rng;
+
+  #[test]
+  fn test_pin_weakness() {
+    for pin in [
+      "1234", "9876", "2468", "0000", "1230", "1212", "123123", "2512", "1225", "1987", "311299",
+      "19991231", "2580",
+    ] {
+      assert!(
+        pin_weakness(pin).unwrap().is_some(),
+        "{} should be weak",
+        pin
+      );
+    }
+    for pin in ["7391", "4086", "594837", "80419326"] {
+      assert!(
+        pin_weakness(pin).unwrap().is_none(),
+        "{} should be accepted",
+        pin
+      );
+    }
+  }
+
+  #[test]
+  fn test_pin_weakness_rejects_non_pins() {
+    for pin in ["", "7", "12a4", "12 4", "١٢٣٤", "12/4"] {
+      assert!(pin_weakness(pin).is_err(), "{:?} should be rejected", pin);
+    }
+  }
+
+  #[test]
+  fn test_process_pin() -> Result<()> {
+    let mut rng = thread_rng();
+    for _ in 0..100 {
+      let pin = process_pin(6, &mut rng)?;
+      assert_eq!(pin.len(), 6);
+      assert!(pin.bytes().all(|b| b.is_ascii_digit()));
+      assert!(pin_weakness(&pin)?.is_none());
+    }
+    assert!(process_pin(3, &mut rng).is_err());
+    Ok(())
+  }
+}
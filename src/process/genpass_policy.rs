@@ -0,0 +1,319 @@
+use anyhow::{Result, bail};
+use rand::RngCore;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::genpass::{
+  GenPassOptions, genpass_entropy_with_minimums, process_genpass_with_minimums,
+};
+
+// the class minimums are built into every password, this is the retry budget
+// for the remaining rules (max_consecutive, forbidden)
+const MAX_ATTEMPTS: usize = 10_000;
+
+/// Password rules of a target system, loaded from a YAML or TOML file.
+///
+/// ```yaml
+/// min_length: 12
+/// max_length: 32
+/// min_uppercase: 1
+/// min_number: 2
+/// min_symbol: 1
+/// max_consecutive: 2
+/// symbols: "!#%+-"
+/// forbidden: [password, acme]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+  pub min_length: Option<u8>,
+  pub max_length: Option<u8>,
+  pub min_uppercase: usize,
+  pub min_lowercase: usize,
+  pub min_number: usize,
+  pub min_symbol: usize,
+  /// Longest allowed run of the same character
+  pub max_consecutive: Option<usize>,
+  /// Substrings that must not appear, compared case-insensitively
+  pub forbidden: Vec<String>,
+  /// Allowed symbols, replaces the default symbol set. Without it any ASCII
+  /// punctuation is allowed
+  pub symbols: Option<String>,
+}
+
+impl PasswordPolicy {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)?;
+    let policy = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("toml") => toml::from_str(&content)?,
+      _ => serde_yaml::from_str(&content)?,
+    };
+    Ok(policy)
+  }
+
+  /// Return the first rule `password` breaks, if any.
+  pub fn violation(&self, password: &str) -> Option<String> {
+    let len = password.chars().count();
+    if let Some(min) = self.min_length
+      && len < min as usize
+    {
+      return Some(format!("shorter than {} characters", min));
+    }
+    if let Some(max) = self.max_length
+      && len > max as usize
+    {
+      return Some(format!("longer than {} characters", max));
+    }
+    if let Some(c) = password.chars().find(|c| !self.allows(*c)) {
+      return Some(format!("contains {:?}, which the policy does not allow", c));
+    }
+
+    let count = |f: fn(&char) -> bool| password.chars().filter(f).count();
+    let classes = [
+      (
+        "uppercase",
+        self.min_uppercase,
+        count(char::is_ascii_uppercase),
+      ),
+      (
+        "lowercase",
+        self.min_lowercase,
+        count(char::is_ascii_lowercase),
+      ),
+      ("number", self.min_number, count(char::is_ascii_digit)),
+      ("symbol", self.min_symbol, count(|c| !c.is_alphanumeric())),
+    ];
+    for (name, min, found) in classes {
+      if found < min {
+        return Some(format!("needs at least {} {} characters", min, name));
+      }
+    }
+
+    if let Some(max) = self.max_consecutive
+      && longest_run(password) > max
+    {
+      return Some(format!("repeats a character more than {} times", max));
+    }
+    let lower = password.to_lowercase();
+    self
+      .forbidden
+      .iter()
+      .find(|word| lower.contains(&word.to_lowercase()))
+      .map(|word| format!("contains forbidden substring {:?}", word))
+  }
+
+  /// Fit the generator options to the policy, failing on rules that no
+  /// password could ever satisfy.
+  pub fn fit(&self, options: &GenPassOptions) -> Result<GenPassOptions> {
+    let min = self.min_length.unwrap_or(0);
+    let max = self.max_length.unwrap_or(u8::MAX);
+    if min > max {
+      bail!(
+        "Policy min_length {} is greater than max_length {}",
+        min,
+        max
+      );
+    }
+    if self.max_consecutive == Some(0) {
+      bail!("Policy max_consecutive must be at least 1");
+    }
+
+    let mut options = options.clone();
+    options.length = options.length.clamp(min, max);
+    if self.symbols.is_some() {
+      options.symbols.clone_from(&self.symbols);
+    }
+    if let Some(c) = options
+      .alphabet
+      .iter()
+      .chain(&options.symbols)
+      .flat_map(|chars| chars.chars())
+      .find(|c| !options.exclude.contains(*c) && !self.allows(*c))
+    {
+      bail!("Policy does not allow {:?} from the alphabet or symbols", c);
+    }
+    let classes = [
+      ("uppercase", self.min_uppercase, options.upper),
+      ("lowercase", self.min_lowercase, options.lower),
+      ("number", self.min_number, options.number),
+      ("symbol", self.min_symbol, options.symbol),
+    ];
+    for (name, min, enabled) in classes {
+      if min > 0 && (!enabled || options.alphabet.is_some()) {
+        bail!("Policy requires {} characters but they are disabled", name);
+      }
+    }
+    let required = classes.iter().map(|(_, min, _)| *min).sum::<usize>();
+    if required > options.length as usize {
+      bail!(
+        "Policy requires {} characters but the password length is {}",
+        required,
+        options.length
+      );
+    }
+    Ok(options)
+  }
+
+  /// Entropy in bits of the passwords generated for this policy.
+  pub fn entropy(&self, options: &GenPassOptions) -> Result<f64> {
+    genpass_entropy_with_minimums(&self.fit(options)?, &self.minimums())
+  }
+
+  // letters and digits, plus the policy's symbols
+  fn allows(&self, c: char) -> bool {
+    c.is_ascii_alphanumeric()
+      || match &self.symbols {
+        Some(symbols) => symbols.contains(c),
+        None => c.is_ascii_punctuation(),
+      }
+  }
+
+  fn minimums(&self) -> [(&'static str, usize); 4] {
+    [
+      ("uppercase", self.min_uppercase),
+      ("lowercase", self.min_lowercase),
+      ("number", self.min_number),
+      ("symbol", self.min_symbol),
+    ]
+  }
+}
+
+/// Generate a password that satisfies every rule of `policy`.
+pub fn process_genpass_with_policy(
+  options: &GenPassOptions,
+  policy: &PasswordPolicy,
+  rng: &mut dyn RngCore,
+) -> Result<String> {
+  let options = policy.fit(options)?;
+  let minimums = policy.minimums();
+  let mut last = String::new();
+  for _ in 0..MAX_ATTEMPTS {
+    let password = process_genpass_with_minimums(&options, &minimums, rng)?;
+    match policy.violation(&password) {
+      None => return Ok(password),
+      Some(reason) => last = reason,
+    }
+  }
+  bail!(
+    "Could not satisfy the policy after {} attempts, last password {}",
+    MAX_ATTEMPTS,
+    last
+  )
+}
+
+fn longest_run(s: &str) -> usize {
+  let mut longest = 0;
+  let mut run = 0;
+  let mut prev = None;
+  for c in s.chars() {
+    run = if prev == Some(c) { run + 1 } else { 1 };
+    longest = longest.max(run);
+    prev = Some(c);
+  }
+  longest
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_policy_violation() {
+    let policy = PasswordPolicy {
+      min_length: Some(8),
+      min_number: 2,
+      max_consecutive: Some(2),
+      forbidden: vec!["acme".into()],
+      ..Default::default()
+    };
+    assert!(policy.violation("abc12").is_some());
+    assert!(policy.violation("abcdefg1").is_some());
+    assert!(policy.violation("abcccd12").is_some());
+    assert!(policy.violation("xxACMExx12").is_some());
+    assert!(policy.violation("abccde12").is_none());
+
+    let symbols = PasswordPolicy {
+      symbols: Some("+-".into()),
+      ..Default::default()
+    };
+    assert!(symbols.violation("abc+12-").is_none());
+    assert!(symbols.violation("abc!12").is_some());
+    assert!(PasswordPolicy::default().violation("abc!12").is_none());
+    assert!(PasswordPolicy::default().violation("abc€12").is_some());
+  }
+
+  #[test]
+  fn test_policy_rejects_foreign_symbols() {
+    let policy: PasswordPolicy = toml::from_str("symbols = \"+-\"").unwrap();
+    let mut rng = rand::thread_rng();
+    let alphabet = GenPassOptions {
+      alphabet: Some("ab!".into()),
+      ..Default::default()
+    };
+    assert!(process_genpass_with_policy(&alphabet, &policy, &mut rng).is_err());
+    let excluded = GenPassOptions {
+      alphabet: Some("ab!".into()),
+      exclude: "!".into(),
+      ..Default::default()
+    };
+    let password = process_genpass_with_policy(&excluded, &policy, &mut rng).unwrap();
+    assert!(password.chars().all(|c| "ab".contains(c)));
+  }
+
+  #[test]
+  fn test_genpass_with_policy() -> Result<()> {
+    let policy: PasswordPolicy = serde_yaml::from_str(
+      "max_length: 12\nmin_number: 3\nmin_symbol: 2\nsymbols: '+-'\nmax_consecutive: 1\n",
+    )?;
+    for _ in 0..20 {
+      let password =
+        process_genpass_with_policy(&GenPassOptions::default(), &policy, &mut rand::thread_rng())?;
+      assert_eq!(password.len(), 12);
+      assert!(policy.violation(&password).is_none());
+      assert!(!password.contains(['!', '@', '#']));
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_genpass_with_high_minimums() -> Result<()> {
+    let policy: PasswordPolicy =
+      serde_yaml::from_str("min_length: 16\nmax_length: 16\nmin_number: 10\nmin_symbol: 3\n")?;
+    let options = GenPassOptions::default();
+    let mut rng = rand::thread_rng();
+    for _ in 0..20 {
+      let password = process_genpass_with_policy(&options, &policy, &mut rng)?;
+      assert_eq!(password.len(), 16);
+      assert!(policy.violation(&password).is_none());
+    }
+    // the minimums shrink the space well below 16 free characters
+    let entropy = policy.entropy(&options)?;
+    let free = GenPassOptions {
+      length: 16,
+      ..Default::default()
+    };
+    assert!(entropy > 40.0);
+    assert!(entropy < genpass_entropy_with_minimums(&free, &[])? - 20.0);
+    Ok(())
+  }
+
+  #[test]
+  fn test_unsatisfiable_policy() {
+    let options = GenPassOptions::default();
+    let too_many: PasswordPolicy = toml::from_str("max_length = 8\nmin_number = 9").unwrap();
+    assert!(process_genpass_with_policy(&options, &too_many, &mut rand::thread_rng()).is_err());
+
+    let no_symbols = GenPassOptions {
+      symbol: false,
+      ..Default::default()
+    };
+    let needs_symbol: PasswordPolicy = toml::from_str("min_symbol = 1").unwrap();
+    assert!(
+      process_genpass_with_policy(&no_symbols, &needs_symbol, &mut rand::thread_rng()).is_err()
+    );
+
+    assert!(toml::from_str::<PasswordPolicy>("min_special = 1").is_err());
+  }
+}
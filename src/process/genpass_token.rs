@@ -0,0 +1,173 @@
+use anyhow::{Result, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+use crate::cli::TokenKind;
+use crate::unix_time_millis;
+
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+// random part of an API key, 30 base62 chars is ~178 bits
+const API_KEY_RANDOM_LEN: usize = 30;
+const API_KEY_CHECKSUM_LEN: usize = 6;
+
+/// Generate a random token.
+///
+/// `bytes` is the amount of randomness for `hex` and `base64` tokens, and
+/// `prefix` the prefix of API keys; both are ignored by the other kinds.
+pub fn process_token(
+  kind: TokenKind,
+  bytes: usize,
+  prefix: &str,
+  rng: &mut dyn RngCore,
+) -> Result<String> {
+  let token = match kind {
+    TokenKind::Hex | TokenKind::Base64 => {
+      if bytes == 0 {
+        bail!("Token size must be at least 1 byte");
+      }
+      let mut buf = vec![0u8; bytes];
+      rng.fill_bytes(&mut buf);
+      match kind {
+        TokenKind::Hex => buf.iter().map(|b| format!("{:02x}", b)).collect(),
+        _ => URL_SAFE_NO_PAD.encode(buf),
+      }
+    }
+    TokenKind::ApiKey => api_key(prefix, rng)?,
+    TokenKind::Uuid => uuid_v4(rng),
+    TokenKind::Uuid7 => uuid_v7(unix_time_millis()?, rng),
+    TokenKind::Ulid => ulid(unix_time_millis()?, rng),
+  };
+  Ok(token)
+}
+
+/// Check the CRC32 suffix of an API key produced by [`process_token`].
+pub fn verify_api_key(key: &str) -> bool {
+  let body = key.rsplit_once('_').map_or(key, |(_, body)| body);
+  if body.len() != API_KEY_RANDOM_LEN + API_KEY_CHECKSUM_LEN || !body.is_ascii() {
+    return false;
+  }
+  let (random, checksum) = body.split_at(API_KEY_RANDOM_LEN);
+  base62_checksum(random) == checksum
+}
+
+// GitHub token style: `<prefix>_<30 random base62><6 base62 crc32 of the random part>`,
+// so typos and truncated keys can be rejected offline and secret scanners can find them.
+fn api_key(prefix: &str, rng: &mut dyn RngCore) -> Result<String> {
+  if !prefix.bytes().all(|b| b.is_ascii_alphanumeric()) {
+    bail!("API key prefix must be alphanumeric");
+  }
+  let random: String = (0..API_KEY_RANDOM_LEN)
+    .map(|_| *BASE62.choose(rng).expect("alphabet won't be empty") as char)
+    .collect();
+  let checksum = base62_checksum(&random);
+  Ok(match prefix {
+    "" => format!("{}{}", random, checksum),
+    _ => format!("{}_{}{}", prefix, random, checksum),
+  })
+}
+
+fn base62_checksum(data: &str) -> String {
+  let mut crc = crc32fast::hash(data.as_bytes());
+  let mut ret = [b'0'; API_KEY_CHECKSUM_LEN];
+  for c in ret.iter_mut().rev() {
+    *c = BASE62[(crc % 62) as usize];
+    crc /= 62;
+  }
+  String::from_utf8_lossy(&ret).into_owned()
+}
+
+fn uuid_v4(rng: &mut dyn RngCore) -> String {
+  let mut bytes = [0u8; 16];
+  rng.fill_bytes(&mut bytes);
+  format_uuid(bytes, 4)
+}
+
+// RFC 9562: 48 bit unix timestamp in milliseconds followed by random bits
+fn uuid_v7(millis: u64, rng: &mut dyn RngCore) -> String {
+  let mut bytes = [0u8; 16];
+  rng.fill_bytes(&mut bytes);
+  bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+  format_uuid(bytes, 7)
+}
+
+fn format_uuid(mut bytes: [u8; 16], version: u8) -> String {
+  bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  )
+}
+
+// 48 bit millisecond timestamp + 80 random bits, as 26 Crockford base32 chars
+fn ulid(millis: u64, rng: &mut dyn RngCore) -> String {
+  let mut random = [0u8; 16];
+  rng.fill_bytes(&mut random[6..]);
+  let value = ((millis as u128 & 0xffff_ffff_ffff) << 80) | u128::from_be_bytes(random);
+  (0..26)
+    .rev()
+    .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::genpass_rng;
+
+  #[test]
+  fn test_random_tokens() -> Result<()> {
+    let mut rng = genpass_rng(None);
+    let hex = process_token(TokenKind::Hex, 16, "", &mut rng)?;
+    assert_eq!(hex.len(), 32);
+    assert!(hex.bytes().all(|b| b.is_ascii_hexdigit()));
+    let b64 = process_token(TokenKind::Base64, 32, "", &mut rng)?;
+    assert_eq!(URL_SAFE_NO_PAD.decode(b64)?.len(), 32);
+    assert!(process_token(TokenKind::Hex, 0, "", &mut rng).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_api_key() -> Result<()> {
+    let mut rng = genpass_rng(None);
+    let key = process_token(TokenKind::ApiKey, 0, "rcli", &mut rng)?;
+    assert!(key.starts_with("rcli_"));
+    assert_eq!(key.len(), 5 + 36);
+    assert!(verify_api_key(&key));
+
+    let mut typo = key.into_bytes();
+    typo[10] = if typo[10] == b'a' { b'b' } else { b'a' };
+    assert!(!verify_api_key(&String::from_utf8(typo)?));
+    assert!(process_token(TokenKind::ApiKey, 0, "bad-prefix", &mut rng).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_uuid() {
+    let mut rng = genpass_rng(None);
+    let v4 = uuid_v4(&mut rng);
+    assert_eq!(v4.len(), 36);
+    assert_eq!(&v4[14..15], "4");
+    assert!("89ab".contains(&v4[19..20]));
+
+    let v7 = uuid_v7(0x0189_1234_5678, &mut rng);
+    assert!(v7.starts_with("01891234-5678-7"));
+  }
+
+  #[test]
+  fn test_ulid() {
+    let mut rng = genpass_rng(None);
+    let id = ulid(1_469_918_176_385, &mut rng);
+    assert_eq!(id.len(), 26);
+    // timestamp example from the ULID spec
+    assert!(id.starts_with("01ARYZ6S41"));
+    assert!(ulid(1, &mut rng) < ulid(2, &mut rng));
+  }
+}
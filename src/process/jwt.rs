@@ -0,0 +1,380 @@
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use sha2::Sha256;
+
+use crate::cli::{JwtAlgorithm, TextSignFormat};
+use crate::{process_sign, process_verify};
+
+// RFC 7518 section 3.2: the HMAC key must be at least as long as the hash
+const HS256_MIN_KEY_LEN: usize = 32;
+
+/// A JWT split into its parts, the signature is not checked.
+#[derive(Debug, Serialize)]
+pub struct DecodedJwt {
+  pub header: Value,
+  pub claims: Value,
+  #[serde(skip)]
+  signing_input: String,
+  #[serde(skip)]
+  signature: Vec<u8>,
+}
+
+/// State of one of the `exp`, `nbf` or `iat` time claims.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeClaim {
+  pub name: &'static str,
+  /// The claim as found in the token, invalid when it isn't a number
+  pub value: Value,
+  pub valid: bool,
+  pub note: String,
+}
+
+/// Extra checks applied by [`process_jwt_verify`] on top of the signature.
+#[derive(Debug, Clone, Default)]
+pub struct JwtValidation {
+  /// Unix time to check the time claims against
+  pub now: u64,
+  /// Allowed clock skew in seconds
+  pub leeway: u64,
+  pub audience: Option<String>,
+  pub issuer: Option<String>,
+}
+
+/// Sign `claims` (a JSON object) into a compact JWT.
+///
+/// HS256 takes the shared secret as `key`, EdDSA the 32 byte Ed25519 signing
+/// key written by `process_generate`.
+pub fn process_jwt_sign(claims: &Value, key: &[u8], alg: JwtAlgorithm) -> Result<String> {
+  if !claims.is_object() {
+    bail!("JWT claims must be a JSON object");
+  }
+  let header = json!({ "alg": alg.to_string(), "typ": "JWT" });
+  let signing_input = format!(
+    "{}.{}",
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?)
+  );
+  let signature = match alg {
+    JwtAlgorithm::Hs256 => hs256(key, &signing_input)?.finalize().into_bytes().to_vec(),
+    JwtAlgorithm::EdDsa => process_sign(
+      &mut signing_input.as_bytes(),
+      ed25519_key(key)?,
+      TextSignFormat::Ed25519,
+    )?,
+  };
+  Ok(format!(
+    "{}.{}",
+    signing_input,
+    URL_SAFE_NO_PAD.encode(signature)
+  ))
+}
+
+/// Split and decode a compact JWT without verifying it.
+pub fn process_jwt_decode(token: &str) -> Result<DecodedJwt> {
+  let token = token.trim();
+  let mut parts = token.split('.');
+  let (Some(header), Some(claims), Some(signature), None) =
+    (parts.next(), parts.next(), parts.next(), parts.next())
+  else {
+    bail!("Invalid JWT: expected 3 dot separated parts");
+  };
+  let decode_json = |part: &str, name: &str| -> Result<Value> {
+    let bytes = URL_SAFE_NO_PAD
+      .decode(part)
+      .map_err(|e| anyhow!("Invalid JWT {}: {}", name, e))?;
+    serde_json::from_slice(&bytes).map_err(|e| anyhow!("Invalid JWT {}: {}", name, e))
+  };
+  Ok(DecodedJwt {
+    header: decode_json(header, "header")?,
+    claims: decode_json(claims, "claims")?,
+    signing_input: format!("{}.{}", header, claims),
+    signature: URL_SAFE_NO_PAD
+      .decode(signature)
+      .map_err(|e| anyhow!("Invalid JWT signature: {}", e))?,
+  })
+}
+
+/// Verify the signature and claims of a JWT, returning the claims.
+///
+/// The algorithm is pinned by the caller, a token whose header names another
+/// one is rejected rather than trusted. EdDSA takes the Ed25519 verifying key.
+pub fn process_jwt_verify(
+  token: &str,
+  key: &[u8],
+  alg: JwtAlgorithm,
+  validation: &JwtValidation,
+) -> Result<Value> {
+  let jwt = process_jwt_decode(token)?;
+  let header_alg = jwt.header["alg"].as_str().unwrap_or_default();
+  if header_alg != alg.to_string() {
+    bail!(
+      "JWT algorithm {:?} does not match the expected {}",
+      header_alg,
+      alg
+    );
+  }
+  let verified = match alg {
+    JwtAlgorithm::Hs256 => hs256(key, &jwt.signing_input)?
+      .verify_slice(&jwt.signature)
+      .is_ok(),
+    JwtAlgorithm::EdDsa => {
+      jwt.signature.len() == 64
+        && process_verify(
+          &mut jwt.signing_input.as_bytes(),
+          ed25519_key(key)?,
+          &jwt.signature,
+          TextSignFormat::Ed25519,
+        )?
+    }
+  };
+  if !verified {
+    bail!("JWT signature not verified");
+  }
+  if let Some(claim) = jwt_time_claims(&jwt.claims, validation.now, validation.leeway)
+    .into_iter()
+    .find(|c| !c.valid)
+  {
+    bail!("JWT {} claim rejected: {}", claim.name, claim.note);
+  }
+  if let Some(issuer) = &validation.issuer
+    && jwt.claims["iss"].as_str() != Some(issuer)
+  {
+    bail!("JWT issuer does not match {}", issuer);
+  }
+  if let Some(audience) = &validation.audience {
+    let matched = match &jwt.claims["aud"] {
+      Value::String(aud) => aud == audience,
+      Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+      _ => false,
+    };
+    if !matched {
+      bail!("JWT audience does not include {}", audience);
+    }
+  }
+  Ok(jwt.claims)
+}
+
+/// Check the `exp`, `nbf` and `iat` claims present in `claims` against `now`,
+/// allowing `leeway` seconds of clock skew.
+pub fn jwt_time_claims(claims: &Value, now: u64, leeway: u64) -> Vec<TimeClaim> {
+  // NumericDate may be fractional (RFC 7519 section 2), and the values come
+  // from an unverified token, so compare as f64 where nothing can overflow
+  let now = now as f64;
+  let leeway = leeway as f64;
+  ["exp", "nbf", "iat"]
+    .into_iter()
+    .filter_map(|name| {
+      let value = claims.get(name)?;
+      let Some(time) = value.as_f64() else {
+        return Some(TimeClaim {
+          name,
+          value: value.clone(),
+          valid: false,
+          note: "not a NumericDate".into(),
+        });
+      };
+      let (valid, note) = match name {
+        "exp" if time + leeway <= now => (false, format!("expired {} ago", human(now - time))),
+        "exp" => (true, format!("expires in {}", human(time - now))),
+        "nbf" if time - leeway > now => (false, format!("not valid for {}", human(time - now))),
+        "nbf" => (true, format!("valid since {} ago", human(now - time))),
+        _ if time - leeway > now => (false, format!("issued {} in the future", human(time - now))),
+        _ => (true, format!("issued {} ago", human(now - time))),
+      };
+      Some(TimeClaim {
+        name,
+        value: value.clone(),
+        valid,
+        note,
+      })
+    })
+    .collect()
+}
+
+/// Build a claims object from `claims` plus the common registered claims,
+/// `iat` defaults to `now`.
+pub fn jwt_claims(
+  claims: Option<&str>,
+  now: u64,
+  expires_in: Option<u64>,
+  registered: &[(&str, Option<&str>)],
+) -> Result<Value> {
+  let mut map: Map<String, Value> = match claims {
+    Some(claims) => serde_json::from_str(claims)
+      .map_err(|e| anyhow!("JWT claims must be a JSON object: {}", e))?,
+    None => Map::new(),
+  };
+  map.entry("iat").or_insert(now.into());
+  if let Some(expires_in) = expires_in {
+    let exp = now
+      .checked_add(expires_in)
+      .ok_or_else(|| anyhow!("JWT expiry is too far in the future"))?;
+    map.insert("exp".into(), exp.into());
+  }
+  for (name, value) in registered {
+    if let Some(value) = value {
+      map.insert(name.to_string(), (*value).into());
+    }
+  }
+  Ok(Value::Object(map))
+}
+
+fn hs256(key: &[u8], signing_input: &str) -> Result<Hmac<Sha256>> {
+  if key.len() < HS256_MIN_KEY_LEN {
+    bail!(
+      "HS256 keys must be at least {} bytes, got {}",
+      HS256_MIN_KEY_LEN,
+      key.len()
+    );
+  }
+  let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)?;
+  mac.update(signing_input.as_bytes());
+  Ok(mac)
+}
+
+fn ed25519_key(key: &[u8]) -> Result<&[u8]> {
+  if key.len() != 32 {
+    bail!("Ed25519 keys must be 32 bytes, got {}", key.len());
+  }
+  Ok(key)
+}
+
+fn human(seconds: f64) -> String {
+  // float to int casts saturate, so absurd values can't overflow
+  match seconds.abs() as u64 {
+    s if s < 60 => format!("{}s", s),
+    s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+    s if s < 86400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+    s => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::process_generate;
+
+  const HS_KEY: &[u8] = include_bytes!("../../fuxtures/blake3.txt");
+  const NOW: u64 = 1_700_000_000;
+
+  fn validation() -> JwtValidation {
+    JwtValidation {
+      now: NOW,
+      leeway: 60,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_hs256_rfc7515_vector() -> Result<()> {
+    // RFC 7515 appendix A.1, with the same key and signing input
+    let key = URL_SAFE_NO_PAD.decode(
+      "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow",
+    )?;
+    let token = "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9.\
+                 eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ.\
+                 dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    let validation = JwtValidation {
+      now: 1_300_000_000,
+      ..Default::default()
+    };
+    let claims = process_jwt_verify(token, &key, JwtAlgorithm::Hs256, &validation)?;
+    assert_eq!(claims["iss"], "joe");
+    Ok(())
+  }
+
+  #[test]
+  fn test_hs256_roundtrip() -> Result<()> {
+    let claims = jwt_claims(
+      Some(r#"{"role":"admin"}"#),
+      NOW,
+      Some(3600),
+      &[("sub", Some("alice")), ("aud", Some("api")), ("iss", None)],
+    )?;
+    let token = process_jwt_sign(&claims, HS_KEY, JwtAlgorithm::Hs256)?;
+    let jwt = process_jwt_decode(&token)?;
+    assert_eq!(jwt.header["alg"], "HS256");
+    assert_eq!(jwt.claims["exp"], NOW + 3600);
+
+    let mut validation = validation();
+    validation.audience = Some("api".into());
+    let ret = process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation)?;
+    assert_eq!(ret["role"], "admin");
+
+    validation.audience = Some("web".into());
+    assert!(process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation).is_err());
+    let mut tampered = token.clone();
+    tampered.pop();
+    tampered.push('A');
+    assert!(
+      process_jwt_verify(&tampered, HS_KEY, JwtAlgorithm::Hs256, &self::validation()).is_err()
+    );
+    assert!(process_jwt_sign(&claims, b"short", JwtAlgorithm::Hs256).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_eddsa_roundtrip() -> Result<()> {
+    let keys = process_generate(TextSignFormat::Ed25519)?;
+    let claims = jwt_claims(None, NOW, None, &[("sub", Some("bob"))])?;
+    let token = process_jwt_sign(&claims, &keys["ed25519.sk"], JwtAlgorithm::EdDsa)?;
+    let ret = process_jwt_verify(
+      &token,
+      &keys["ed25519.pk"],
+      JwtAlgorithm::EdDsa,
+      &validation(),
+    )?;
+    assert_eq!(ret["sub"], "bob");
+    // the expected algorithm is pinned, the header can't downgrade it
+    assert!(process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation()).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_time_claims() -> Result<()> {
+    let now = NOW as i64;
+    let claims = json!({ "exp": now - 30, "nbf": now + 120, "iat": now - 3700 });
+    let ret = jwt_time_claims(&claims, NOW, 60);
+    assert_eq!(ret.len(), 3);
+    assert!(ret[0].valid, "exp within leeway");
+    assert!(!ret[1].valid);
+    assert_eq!(ret[1].note, "not valid for 2m 0s");
+    assert_eq!(ret[2].note, "issued 1h 1m ago");
+
+    let token = process_jwt_sign(&claims, HS_KEY, JwtAlgorithm::Hs256)?;
+    assert!(process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation()).is_err());
+    assert!(process_jwt_decode("a.b").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_time_claims_not_integers() -> Result<()> {
+    // fractional NumericDates are valid and still checked
+    let claims = json!({ "exp": NOW as f64 - 3600.5 });
+    let ret = jwt_time_claims(&claims, NOW, 60);
+    assert!(!ret[0].valid);
+    let token = process_jwt_sign(&claims, HS_KEY, JwtAlgorithm::Hs256)?;
+    assert!(process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation()).is_err());
+
+    // a string can't be checked, so the token is rejected
+    let claims = json!({ "exp": (NOW + 3600).to_string() });
+    assert!(!jwt_time_claims(&claims, NOW, 60)[0].valid);
+    let token = process_jwt_sign(&claims, HS_KEY, JwtAlgorithm::Hs256)?;
+    assert!(process_jwt_verify(&token, HS_KEY, JwtAlgorithm::Hs256, &validation()).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_time_claims_extreme_values() {
+    let claims = json!({ "exp": i64::MIN, "nbf": i64::MIN, "iat": u64::MAX });
+    let ret = jwt_time_claims(&claims, NOW, u64::MAX);
+    assert_eq!(ret.len(), 3);
+    let ret = jwt_time_claims(&claims, u64::MAX, 0);
+    assert!(!ret[0].valid);
+    assert!(ret[1].valid);
+    assert!(jwt_claims(None, u64::MAX, Some(1), &[]).is_err());
+  }
+}
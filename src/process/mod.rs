@@ -1,12 +1,57 @@
 mod b64;
+mod codec;
 mod csv;
+mod csv_fmt;
+mod csv_mask;
+mod escape;
 mod genpass;
+mod genpass_breach;
+mod genpass_check;
+mod genpass_pattern;
+mod genpass_pin;
+mod genpass_policy;
+mod genpass_token;
 mod http;
+mod jwt;
+mod otp;
 mod text;
 
+pub use b64::hexdump;
 pub use b64::process_decode;
+pub use b64::process_decode_lenient;
 pub use b64::process_encode;
-pub use csv::process_csv;
-pub use genpass::process_genpass;
+pub use b64::{
+  DataUri, process_decode_percent, process_encode_data_uri, read_data_uri_header, sniff_mime,
+};
+pub use codec::{process_codec_decode, process_codec_encode};
+pub use csv::{
+  ColumnarOptions, CsvRecords, DEFAULT_ROW_GROUP_SIZE, deserialize_records, process_csv,
+  process_csv_with_options, read_records, write_columnar, write_records,
+};
+pub use csv_fmt::{CsvFmtOptions, process_csv_fmt};
+pub use csv_mask::process_csv_mask;
+pub use escape::{
+  process_html_escape, process_html_unescape, process_qp_decode, process_qp_encode,
+  process_url_decode, process_url_encode,
+};
+pub use genpass::{
+  GenPassOptions, Passphrase, PassphraseOptions, PasswordReport, genpass_entropy, genpass_rng,
+  password_report, process_genpass, process_genpass_with_rng, process_passphrase,
+  process_passphrase_with_rng,
+};
+pub use genpass_breach::BreachList;
+pub use genpass_check::{CrackTimes, StrengthReport, process_check_passwords};
+pub use genpass_pattern::{process_pattern, process_pronounceable};
+pub use genpass_pin::{pin_weakness, process_pin};
+pub use genpass_policy::{PasswordPolicy, process_genpass_with_policy};
+pub use genpass_token::{process_token, verify_api_key};
 pub use http::process_http_serve;
+pub use jwt::{
+  DecodedJwt, JwtValidation, TimeClaim, jwt_claims, jwt_time_claims, process_jwt_decode,
+  process_jwt_sign, process_jwt_verify,
+};
+pub use otp::{
+  OtpOptions, decode_otp_secret, hotp, otpauth_uri, process_otp_secret, totp, verify_hotp,
+  verify_totp,
+};
 pub use text::{decrypt_text, encrypt_text, process_generate, process_sign, process_verify};
@@ -0,0 +1,228 @@
+use anyhow::{Result, anyhow, bail};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::cli::OtpAlgorithm;
+
+/// Parameters shared by every HOTP/TOTP computation.
+#[derive(Debug, Clone, Copy)]
+pub struct OtpOptions {
+  pub algorithm: OtpAlgorithm,
+  pub digits: u32,
+  /// TOTP time step in seconds, unused by HOTP
+  pub period: u64,
+}
+
+/// Generate a random secret of `bytes` bytes, base32 encoded without padding.
+pub fn process_otp_secret(bytes: usize) -> String {
+  let mut secret = vec![0u8; bytes];
+  OsRng.fill_bytes(&mut secret);
+  BASE32_NOPAD.encode(&secret)
+}
+
+/// Build the `otpauth://` URI understood by authenticator apps.
+///
+/// `counter` selects an HOTP URI, otherwise a TOTP one is built.
+pub fn otpauth_uri(
+  secret: &str,
+  issuer: &str,
+  account: &str,
+  counter: Option<u64>,
+  options: OtpOptions,
+) -> String {
+  let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+  let account = utf8_percent_encode(account, NON_ALPHANUMERIC).to_string();
+  let mut uri = format!(
+    "otpauth://{}/{}:{}?secret={}&issuer={}&algorithm={}&digits={}",
+    if counter.is_some() { "hotp" } else { "totp" },
+    issuer,
+    account,
+    secret,
+    issuer,
+    options.algorithm.to_string().to_uppercase(),
+    options.digits
+  );
+  match counter {
+    Some(counter) => uri.push_str(&format!("&counter={}", counter)),
+    None => uri.push_str(&format!("&period={}", options.period)),
+  }
+  uri
+}
+
+/// Decode a base32 secret, tolerating lowercase, spaces and padding.
+pub fn decode_otp_secret(secret: &str) -> Result<Vec<u8>> {
+  let secret: String = secret
+    .chars()
+    .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+    .collect::<String>()
+    .to_uppercase();
+  BASE32_NOPAD
+    .decode(secret.as_bytes())
+    .map_err(|e| anyhow!("Invalid base32 secret: {}", e))
+}
+
+/// HOTP value for `counter`, RFC 4226.
+pub fn hotp(secret: &[u8], counter: u64, options: OtpOptions) -> Result<String> {
+  if !(6..=8).contains(&options.digits) {
+    bail!("OTP digits must be between 6 and 8");
+  }
+  let msg = counter.to_be_bytes();
+  let digest = match options.algorithm {
+    OtpAlgorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(secret, &msg)?,
+    OtpAlgorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(secret, &msg)?,
+    OtpAlgorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(secret, &msg)?,
+  };
+  // dynamic truncation, RFC 4226 section 5.3
+  let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+  let code = u32::from_be_bytes(digest[offset..offset + 4].try_into()?) & 0x7fff_ffff;
+  let code = code % 10u32.pow(options.digits);
+  Ok(format!("{:0width$}", code, width = options.digits as usize))
+}
+
+/// TOTP value at unix time `time`, RFC 6238.
+pub fn totp(secret: &[u8], time: u64, options: OtpOptions) -> Result<String> {
+  if options.period == 0 {
+    bail!("TOTP period must be greater than 0");
+  }
+  hotp(secret, time / options.period, options)
+}
+
+/// Check `code` against the TOTP values within `skew` time steps of `time`,
+/// returning the matching step offset.
+pub fn verify_totp(
+  secret: &[u8],
+  code: &str,
+  time: u64,
+  skew: u64,
+  options: OtpOptions,
+) -> Result<Option<i64>> {
+  if options.period == 0 {
+    bail!("TOTP period must be greater than 0");
+  }
+  let step = time / options.period;
+  for counter in step.saturating_sub(skew)..=step.saturating_add(skew) {
+    if constant_time_eq(&hotp(secret, counter, options)?, code) {
+      return Ok(Some(counter as i64 - step as i64));
+    }
+  }
+  Ok(None)
+}
+
+/// Check `code` against the HOTP values for `counter` and the next `look_ahead`
+/// counters, returning the matching counter.
+pub fn verify_hotp(
+  secret: &[u8],
+  code: &str,
+  counter: u64,
+  look_ahead: u64,
+  options: OtpOptions,
+) -> Result<Option<u64>> {
+  for counter in counter..=counter.saturating_add(look_ahead) {
+    if constant_time_eq(&hotp(secret, counter, options)?, code) {
+      return Ok(Some(counter));
+    }
+  }
+  Ok(None)
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(key: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+  let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(key)?;
+  mac.update(msg);
+  Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+  a.len() == b.len()
+    && a
+      .bytes()
+      .zip(b.bytes())
+      .fold(0, |acc, (x, y)| acc | (x ^ y))
+      == 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SHA1_SECRET: &[u8] = b"12345678901234567890";
+  const SHA256_SECRET: &[u8] = b"12345678901234567890123456789012";
+  const SHA512_SECRET: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+  fn options(algorithm: OtpAlgorithm, digits: u32) -> OtpOptions {
+    OtpOptions {
+      algorithm,
+      digits,
+      period: 30,
+    }
+  }
+
+  #[test]
+  fn test_hotp_rfc4226() -> Result<()> {
+    let expected = ["755224", "287082", "359152", "969429", "338314"];
+    for (counter, code) in expected.iter().enumerate() {
+      let ret = hotp(SHA1_SECRET, counter as u64, options(OtpAlgorithm::Sha1, 6))?;
+      assert_eq!(&ret, code);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_totp_rfc6238() -> Result<()> {
+    let cases = [
+      (59, "94287082", "46119246", "90693936"),
+      (1111111109, "07081804", "68084774", "25091201"),
+      (20000000000, "65353130", "77737706", "47863826"),
+    ];
+    for (time, sha1, sha256, sha512) in cases {
+      assert_eq!(
+        totp(SHA1_SECRET, time, options(OtpAlgorithm::Sha1, 8))?,
+        sha1
+      );
+      assert_eq!(
+        totp(SHA256_SECRET, time, options(OtpAlgorithm::Sha256, 8))?,
+        sha256
+      );
+      assert_eq!(
+        totp(SHA512_SECRET, time, options(OtpAlgorithm::Sha512, 8))?,
+        sha512
+      );
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_verify() -> Result<()> {
+    let opts = options(OtpAlgorithm::Sha1, 8);
+    assert_eq!(verify_totp(SHA1_SECRET, "94287082", 89, 1, opts)?, Some(-1));
+    assert_eq!(verify_totp(SHA1_SECRET, "94287082", 119, 1, opts)?, None);
+    let opts = options(OtpAlgorithm::Sha1, 6);
+    assert_eq!(verify_hotp(SHA1_SECRET, "969429", 1, 3, opts)?, Some(3));
+    assert_eq!(verify_hotp(SHA1_SECRET, "969429", 1, 1, opts)?, None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_secret_roundtrip() -> Result<()> {
+    let secret = process_otp_secret(20);
+    assert_eq!(secret.len(), 32);
+    assert_eq!(decode_otp_secret(&secret.to_lowercase())?.len(), 20);
+    assert_eq!(decode_otp_secret("GEZD GNBV GY3T QOJQ")?, b"1234567890");
+    let uri = otpauth_uri(
+      "ABC",
+      "Acme Co",
+      "alice@acme.io",
+      None,
+      options(OtpAlgorithm::Sha1, 6),
+    );
+    assert_eq!(
+      uri,
+      "otpauth://totp/Acme%20Co:alice%40acme%2Eio?secret=ABC&issuer=Acme%20Co&algorithm=SHA1&digits=6&period=30"
+    );
+    Ok(())
+  }
+}
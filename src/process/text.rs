@@ -1,5 +1,5 @@
 use crate::TextSignFormat;
-use crate::process_genpass;
+use crate::{GenPassOptions, process_genpass};
 use anyhow::{Result, anyhow};
 use base64::{Engine, engine::general_purpose::STANDARD};
 use chacha20poly1305::{
@@ -8,9 +8,10 @@ use chacha20poly1305::{
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
+use sha2::{Digest, Sha512};
 use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
 use std::str;
 
@@ -33,7 +34,7 @@ trait KeyGenerator {
   fn generate() -> Result<HashMap<&'static str, Vec<u8>>>;
 }
 
-struct Blake3 {
+pub(crate) struct Blake3 {
   key: [u8; 32],
 }
 
@@ -45,10 +46,20 @@ struct Ed25519Verifier {
   key: VerifyingKey,
 }
 
+// Ed25519ph signs a SHA-512 digest of the input, so it can be streamed
+struct Ed25519phSigner {
+  key: SigningKey,
+}
+
+struct Ed25519phVerifier {
+  key: VerifyingKey,
+}
+
 pub fn process_sign(reader: &mut dyn Read, key: &[u8], format: TextSignFormat) -> Result<Vec<u8>> {
   let signer: Box<dyn TextSign> = match format {
     TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
     TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
+    TextSignFormat::Ed25519ph => Box::new(Ed25519phSigner::try_new(key)?),
   };
   signer.sign(reader)
 }
@@ -62,6 +73,7 @@ pub fn process_verify(
   let verifier: Box<dyn TextVerify> = match format {
     TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
     TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+    TextSignFormat::Ed25519ph => Box::new(Ed25519phVerifier::try_new(key)?),
   };
   verifier.verify(reader, sig)
 }
@@ -69,7 +81,7 @@ pub fn process_verify(
 pub fn process_generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
   match format {
     TextSignFormat::Blake3 => Blake3::generate(),
-    TextSignFormat::Ed25519 => Ed25519Signer::generate(),
+    TextSignFormat::Ed25519 | TextSignFormat::Ed25519ph => Ed25519Signer::generate(),
   }
 }
 
@@ -121,20 +133,20 @@ pub fn decrypt_text(ciphertext_base64: &str, key_base64: &str) -> anyhow::Result
 
 impl TextSign for Blake3 {
   fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
-    let ret = blake3::keyed_hash(&self.key, &buf);
+    let ret = self.hash_reader(reader)?;
     Ok(ret.as_bytes().to_vec())
   }
 }
 
 impl TextVerify for Blake3 {
   fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
-    let ret = blake3::keyed_hash(&self.key, &buf);
-    let hash = ret.as_bytes();
-    Ok(hash == sig)
+    let ret = self.hash_reader(reader)?;
+    // blake3::Hash compares in constant time
+    let ret = match <[u8; 32]>::try_from(sig) {
+      Ok(sig) => ret == blake3::Hash::from_bytes(sig),
+      Err(_) => false,
+    };
+    Ok(ret)
   }
 }
 
@@ -158,13 +170,30 @@ impl TextVerify for Ed25519Verifier {
   fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let sig = (&sig[..64]).try_into()?;
-    let signature = Signature::from_bytes(sig);
+    let signature = Signature::from_slice(sig)?;
     let ret = self.key.verify(&buf, &signature).is_ok();
     Ok(ret)
   }
 }
 
+impl TextSign for Ed25519phSigner {
+  fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let sig = self.key.sign_prehashed(prehash(reader)?, None)?;
+    Ok(sig.to_bytes().to_vec())
+  }
+}
+
+impl TextVerify for Ed25519phVerifier {
+  fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+    let signature = Signature::from_slice(sig)?;
+    let ret = self
+      .key
+      .verify_prehashed(prehash(reader)?, None, &signature)
+      .is_ok();
+    Ok(ret)
+  }
+}
+
 impl KeyLoader for Ed25519Signer {
   fn load(path: impl AsRef<Path>) -> Result<Self> {
     let key = fs::read(path)?;
@@ -184,17 +213,30 @@ impl Blake3 {
     Self { key }
   }
 
+  pub fn hash(&self, data: &[u8]) -> blake3::Hash {
+    blake3::keyed_hash(&self.key, data)
+  }
+
+  fn hash_reader(&self, reader: &mut dyn Read) -> Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new_keyed(&self.key);
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize())
+  }
+
   pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-    let key = key.as_ref();
-    let key = (&key[..32]).try_into()?;
-    let signer = Self::new(key);
+    let key = key_prefix(key.as_ref())?;
+    let signer = Self::new(*key);
     Ok(signer)
   }
 }
 
 impl KeyGenerator for Blake3 {
   fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-    let key = process_genpass(32, true, true, true, true)?;
+    let options = GenPassOptions {
+      length: 32,
+      ..Default::default()
+    };
+    let key = process_genpass(&options)?;
     let mut map = HashMap::new();
     map.insert("blake3.txt", key.as_bytes().to_vec());
     Ok(map)
@@ -219,21 +261,47 @@ impl Ed25519Signer {
     Self { key }
   }
   pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-    let key = key.as_ref();
-    let key = (&key[..32]).try_into()?;
+    let key = key_prefix(key.as_ref())?;
     Ok(Self::new(key))
   }
 }
 
 impl Ed25519Verifier {
   pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-    let key = key.as_ref();
-    let key = (&key[..32]).try_into()?;
+    let key = key_prefix(key.as_ref())?;
     let key = VerifyingKey::from_bytes(key)?;
     Ok(Self { key })
   }
 }
 
+impl Ed25519phSigner {
+  pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
+    let Ed25519Signer { key } = Ed25519Signer::try_new(key)?;
+    Ok(Self { key })
+  }
+}
+
+impl Ed25519phVerifier {
+  pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
+    let Ed25519Verifier { key } = Ed25519Verifier::try_new(key)?;
+    Ok(Self { key })
+  }
+}
+
+// key files may carry a trailing newline, only the first 32 bytes are the key
+fn key_prefix(key: &[u8]) -> Result<&[u8; 32]> {
+  let key = key
+    .get(..32)
+    .ok_or_else(|| anyhow!("Key must be at least 32 bytes, got {}", key.len()))?;
+  Ok(key.try_into()?)
+}
+
+fn prehash(reader: &mut dyn Read) -> Result<Sha512> {
+  let mut hasher = Sha512::new();
+  io::copy(reader, &mut hasher)?;
+  Ok(hasher)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -261,4 +329,60 @@ mod tests {
     assert!(ret);
     Ok(())
   }
+
+  #[test]
+  fn test_short_key_is_an_error() {
+    let key = &KEY[..31];
+    assert!(Blake3::try_new(key).is_err());
+    assert!(process_sign(&mut "hello".as_bytes(), key, TextSignFormat::Ed25519).is_err());
+    assert!(
+      process_verify(
+        &mut "hello".as_bytes(),
+        key,
+        &[0; 64],
+        TextSignFormat::Ed25519
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_blake3_streaming() -> Result<()> {
+    // larger than the io::copy buffer, so the hasher sees several chunks
+    let data = vec![7u8; 100_000];
+    let sig = process_sign(&mut &data[..], KEY, TextSignFormat::Blake3)?;
+    assert_eq!(sig, Blake3::try_new(KEY)?.hash(&data).as_bytes());
+    assert!(!process_verify(
+      &mut &data[1..],
+      KEY,
+      &sig,
+      TextSignFormat::Blake3
+    )?);
+    assert!(!process_verify(
+      &mut &data[..],
+      KEY,
+      &sig[..31],
+      TextSignFormat::Blake3
+    )?);
+    Ok(())
+  }
+
+  #[test]
+  fn test_ed25519ph_sign_verify() -> Result<()> {
+    let sk = include_bytes!("../../fuxtures/ed25519.sk");
+    let pk = include_bytes!("../../fuxtures/ed25519.pk");
+    let data = vec![42u8; 100_000];
+    let format = TextSignFormat::Ed25519ph;
+    let sig = process_sign(&mut &data[..], sk, format)?;
+    assert!(process_verify(&mut &data[..], pk, &sig, format)?);
+    assert!(!process_verify(&mut &data[1..], pk, &sig, format)?);
+    // prehashed and pure signatures are not interchangeable
+    assert!(!process_verify(
+      &mut &data[..],
+      pk,
+      &sig,
+      TextSignFormat::Ed25519
+    )?);
+    Ok(())
+  }
 }
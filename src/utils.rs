@@ -1,6 +1,10 @@
 use anyhow::Result;
-use std::fs::File;
-use std::io::Read;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEXDUMP_PREVIEW_LEN: usize = 256;
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
   let reader: Box<dyn Read> = if input == "-" {
@@ -17,3 +21,65 @@ pub fn get_content(input: &str) -> Result<Vec<u8>> {
   reader.read_to_end(&mut buf)?;
   Ok(buf)
 }
+
+/// Buffered writer to `output`, or to stdout when it's `None`.
+pub fn get_writer(output: Option<&Path>) -> Result<Box<dyn Write>> {
+  let writer: Box<dyn Write> = match output {
+    Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+    None => Box::new(BufWriter::new(std::io::stdout().lock())),
+  };
+  Ok(writer)
+}
+
+/// Write decoded bytes to `output` or stdout. Raw binary would garble an
+/// interactive terminal, so non UTF-8 data is shown as a hexdump preview there.
+pub fn write_decoded(data: &[u8], output: Option<&Path>) -> Result<()> {
+  if output.is_none() && std::io::stdout().is_terminal() && std::str::from_utf8(data).is_err() {
+    print!(
+      "{}",
+      crate::hexdump(&data[..data.len().min(HEXDUMP_PREVIEW_LEN)])
+    );
+    eprintln!(
+      "Binary data ({} bytes) not printed to the terminal, use --output or redirect stdout",
+      data.len()
+    );
+    return Ok(());
+  }
+  let mut writer = get_writer(output)?;
+  writer.write_all(data)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Write a secret to `path`, readable and writable by the owner only (0600 on unix).
+pub fn write_secret_file(path: impl AsRef<Path>, content: &[u8]) -> Result<()> {
+  let mut options = OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    options.mode(0o600);
+    let file = options.open(path.as_ref())?;
+    // mode only applies to newly created files, tighten an existing one too
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    write_all(file, content)
+  }
+  #[cfg(not(unix))]
+  write_all(options.open(path.as_ref())?, content)
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix_time() -> Result<u64> {
+  Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn unix_time_millis() -> Result<u64> {
+  Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+fn write_all(mut file: File, content: &[u8]) -> Result<()> {
+  file.write_all(content)?;
+  file.flush()?;
+  Ok(())
+}